@@ -0,0 +1,28 @@
+use charlcd::bar_graph::BarGraph;
+use charlcd::Screen;
+use std::io::Write;
+
+use std::{thread, time};
+
+fn main() -> std::io::Result<()> {
+    let mut screen = Screen::default()?;
+
+    let graph = BarGraph::new(8, 2).spacing(1);
+    graph.load_glyphs(&mut screen)?;
+
+    screen.clear()?;
+    screen.flush()?;
+
+    // animate a fake spectrum
+    for step in 0..100 {
+        let values: Vec<f32> = (0..8)
+            .map(|i| ((step + i * 3) % 16) as f32 / 15.0)
+            .collect();
+        graph.draw(&mut screen, 0, 0, &values)?;
+        screen.flush()?;
+
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use charlcd::big_digit::BigDigits;
+use charlcd::Screen;
+use std::io::Write;
+
+fn main() -> std::io::Result<()> {
+    let mut screen = Screen::default()?;
+
+    let height = screen.height()?;
+    let big = BigDigits::new(height);
+    big.load_glyphs(&mut screen)?;
+
+    screen.clear()?;
+    big.draw(&mut screen, 0, 0, "12:45")?;
+    screen.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! Draw vertical bar graphs (audio meters, per-core CPU load, etc.)
+//!
+//! Each character cell of a bar has a resolution of 8 pixels in height. The
+//! partial levels are drawn with custom characters stored in slots `0` to `6`
+//! of the screen memory, while full cells use the built-in
+//! [`special_char::BLOCK`][crate::special_char::BLOCK] character. A bar can be
+//! stacked over multiple rows (e.g. on 4 lines displays) to get a finer
+//! resolution.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::bar_graph::BarGraph;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!
+//!     // 4 bars of 2 rows each, that is 16 levels per bar
+//!     let graph = BarGraph::new(4, 2);
+//!     graph.load_glyphs(&mut screen)?;
+//!
+//!     screen.clear()?;
+//!     graph.draw(&mut screen, 0, 0, &[0.1, 0.5, 0.75, 1.0])?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Write;
+
+use crate::special_char;
+use crate::Screen;
+
+/// Number of pixel rows in a character cell.
+pub const CELL_LEVELS: u32 = 8;
+
+/// Custom character of a cell filled with `level` pixel rows, from the bottom.
+///
+/// A `level` of 0 gives an empty character and a level greater or equal to 8
+/// gives a fully filled character.
+pub fn level_glyph(level: u8) -> [u8; 8] {
+    let mut glyph = [0u8; 8];
+    for (i, row) in glyph.iter_mut().enumerate() {
+        if i + level as usize >= 8 {
+            *row = 0b11111;
+        }
+    }
+    glyph
+}
+
+/// A set of vertical bars drawn side by side.
+pub struct BarGraph {
+    bars: u32,
+    rows: u32,
+    spacing: u32,
+}
+
+impl BarGraph {
+    /// Create a new graph of `bars` vertical bars, each of them `rows`
+    /// characters high.
+    pub fn new(bars: u32, rows: u32) -> BarGraph {
+        BarGraph {
+            bars,
+            rows: rows.max(1),
+            spacing: 0,
+        }
+    }
+
+    /// Leave `spacing` empty columns between two consecutive bars.
+    pub fn spacing(mut self, spacing: u32) -> BarGraph {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Number of distinct levels a single bar can display, empty bar excluded.
+    pub fn levels(&self) -> u32 {
+        self.rows * CELL_LEVELS
+    }
+
+    /// Width of the whole graph, in characters.
+    pub fn width(&self) -> u32 {
+        if self.bars == 0 {
+            return 0;
+        }
+        self.bars + (self.bars - 1) * self.spacing
+    }
+
+    /// Height of the whole graph, in characters.
+    pub fn height(&self) -> u32 {
+        self.rows
+    }
+
+    /// Store the partial level characters into the screen memory, slots `0`
+    /// to `6`.
+    ///
+    /// This has to be called once before drawing, and again if the slots were
+    /// overwritten with other custom characters in the meantime.
+    pub fn load_glyphs<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        for level in 1..CELL_LEVELS as u8 {
+            screen.custom_char(level - 1, level_glyph(level))?;
+        }
+        Ok(())
+    }
+
+    /// Draw the bars with their top-left corner at the (`x`, `y`) position.
+    ///
+    /// Each value is the filling ratio of the matching bar, between `0.0` and
+    /// `1.0`. Out of range values are clamped, extra values are ignored and
+    /// missing values are drawn as empty bars.
+    pub fn draw<T: Write>(
+        &self,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+        values: &[f32],
+    ) -> std::io::Result<()> {
+        let levels: Vec<u32> = (0..self.bars as usize)
+            .map(|i| self.level_of(values.get(i).copied().unwrap_or(0.0)))
+            .collect();
+
+        for row in 0..self.rows {
+            // number of levels below this row
+            let base = (self.rows - 1 - row) * CELL_LEVELS;
+
+            let mut line = Vec::with_capacity(self.width() as usize);
+            for (i, level) in levels.iter().enumerate() {
+                if i > 0 {
+                    line.extend((0..self.spacing).map(|_| b' '));
+                }
+                line.push(cell_char(level.saturating_sub(base)));
+            }
+
+            screen.gotoxy(x, y + row)?;
+            screen.write_all(&line)?;
+        }
+
+        Ok(())
+    }
+
+    fn level_of(&self, value: f32) -> u32 {
+        let value = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, 1.0)
+        };
+        (value * self.levels() as f32).round() as u32
+    }
+}
+
+/// Character to print for a cell filled with `level` pixel rows.
+fn cell_char(level: u32) -> u8 {
+    match level {
+        0 => b' ',
+        l if l >= CELL_LEVELS => special_char::BLOCK,
+        l => l as u8 - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_fill_from_bottom() {
+        assert_eq!(level_glyph(0), [0; 8]);
+        assert_eq!(level_glyph(2), [0, 0, 0, 0, 0, 0, 0b11111, 0b11111]);
+        assert_eq!(level_glyph(8), [0b11111; 8]);
+    }
+
+    #[test]
+    fn draw_single_row() {
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        BarGraph::new(3, 1)
+            .draw(&mut screen, 1, 2, &[0.0, 0.5, 2.0])
+            .unwrap();
+
+        assert_eq!(buf, b"\x1b[Lx1y2; \x03\xff");
+    }
+
+    #[test]
+    fn draw_stacked_rows() {
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        BarGraph::new(2, 2)
+            .spacing(1)
+            .draw(&mut screen, 0, 0, &[0.25, 0.75])
+            .unwrap();
+
+        assert_eq!(buf, b"\x1b[Lx0y0;  \x03\x1b[Lx0y1;\x03 \xff");
+    }
+}
@@ -0,0 +1,371 @@
+//! Draw big digits spanning two to four rows, readable from a distance.
+//!
+//! Digits are built like seven-segment displays out of the built-in
+//! [`special_char::BLOCK`][crate::special_char::BLOCK] character and of a
+//! shared set of 8 custom segment characters, stored in slots `0` to `7` of
+//! the screen memory.
+//!
+//! The supported characters are the digits `0` to `9`, `:`, `-`, `.` and
+//! space. Digits, minus and space are 3 columns wide while the colon and the
+//! decimal point take a single column. One empty column is left between two
+//! consecutive characters.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::big_digit::BigDigits;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!
+//!     let big = BigDigits::new(4); // use the whole 20x4 screen height
+//!     big.load_glyphs(&mut screen)?;
+//!
+//!     screen.clear()?;
+//!     big.draw(&mut screen, 1, 0, "12:45")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::cmp::Ordering;
+use std::io::{Error, ErrorKind, Write};
+
+use crate::special_char;
+use crate::Screen;
+
+/// Minimum number of rows of a big digit.
+pub const MIN_ROWS: u32 = 2;
+/// Maximum number of rows of a big digit.
+pub const MAX_ROWS: u32 = 4;
+
+// Cells are described as a mask of their filled pixel rows, bit 0 being the
+// top row. Partially filled rows only exist for the dots.
+const FULL: u8 = 0xff;
+const TOP: u8 = 0b0000_0011;
+const CENTER: u8 = 0b0001_1000;
+const BOTTOM: u8 = 0b1100_0000;
+const UPPER: u8 = 0b0001_1111;
+const LOWER: u8 = 0b1111_1000;
+
+// Custom characters, in screen memory order
+const SEGMENTS: [u8; 6] = [TOP, CENTER, BOTTOM, TOP | BOTTOM, UPPER, LOWER];
+const DOT_SLOT: u8 = 6;
+const DOT_LOW_SLOT: u8 = 7;
+
+// Seven-segment encoding: a (top), b (upper right), c (lower right),
+// d (bottom), e (lower left), f (upper left), g (middle).
+const SEG_A: u8 = 1 << 0;
+const SEG_B: u8 = 1 << 1;
+const SEG_C: u8 = 1 << 2;
+const SEG_D: u8 = 1 << 3;
+const SEG_E: u8 = 1 << 4;
+const SEG_F: u8 = 1 << 5;
+const SEG_G: u8 = 1 << 6;
+
+const DIGITS: [u8; 10] = [
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F,
+    SEG_B | SEG_C,
+    SEG_A | SEG_B | SEG_D | SEG_E | SEG_G,
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_G,
+    SEG_B | SEG_C | SEG_F | SEG_G,
+    SEG_A | SEG_C | SEG_D | SEG_F | SEG_G,
+    SEG_A | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G,
+    SEG_A | SEG_B | SEG_C,
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G,
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_F | SEG_G,
+];
+
+/// Custom character of a cell filled on the rows of `mask`.
+fn mask_glyph(mask: u8) -> [u8; 8] {
+    let mut glyph = [0u8; 8];
+    for (i, row) in glyph.iter_mut().enumerate() {
+        if mask & (1 << i) != 0 {
+            *row = 0b11111;
+        }
+    }
+    glyph
+}
+
+/// Character to print for a cell filled on the rows of `mask`.
+fn mask_char(mask: u8) -> u8 {
+    match mask {
+        0 => b' ',
+        FULL => special_char::BLOCK,
+        mask => SEGMENTS
+            .iter()
+            .position(|&m| m == mask)
+            .expect("segment mask has no glyph") as u8,
+    }
+}
+
+/// Renderer of big digits of a given height.
+pub struct BigDigits {
+    rows: u32,
+}
+
+impl BigDigits {
+    /// Create a new renderer for digits `rows` characters high.
+    ///
+    /// The value is clamped between [`MIN_ROWS`] and [`MAX_ROWS`].
+    pub fn new(rows: u32) -> BigDigits {
+        BigDigits {
+            rows: rows.clamp(MIN_ROWS, MAX_ROWS),
+        }
+    }
+
+    /// Height of the rendered text, in characters.
+    pub fn height(&self) -> u32 {
+        self.rows
+    }
+
+    /// Width of `text` once rendered, in characters.
+    ///
+    /// Unsupported characters are not taken into account.
+    pub fn width(&self, text: &str) -> u32 {
+        let widths: Vec<u32> = text.chars().filter_map(char_width).collect();
+        if widths.is_empty() {
+            return 0;
+        }
+        widths.iter().sum::<u32>() + widths.len() as u32 - 1
+    }
+
+    /// Store the segment characters into the screen memory, slots `0` to
+    /// `7`.
+    ///
+    /// This has to be called once before drawing, and again if the slots were
+    /// overwritten with other custom characters in the meantime.
+    pub fn load_glyphs<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        for (slot, mask) in SEGMENTS.iter().enumerate() {
+            screen.custom_char(slot as u8, mask_glyph(*mask))?;
+        }
+
+        #[rustfmt::skip]
+        let dot = [
+            0b00000,
+            0b00000,
+            0b00000,
+            0b01110,
+            0b01110,
+            0b00000,
+            0b00000,
+            0b00000,
+        ];
+        screen.custom_char(DOT_SLOT, dot)?;
+        screen.custom_char(DOT_LOW_SLOT, [0, 0, 0, 0, 0, 0, 0b01110, 0b01110])?;
+        Ok(())
+    }
+
+    /// Draw `text` with its top-left corner at the (`x`, `y`) position.
+    ///
+    /// An error of kind [`ErrorKind::InvalidInput`] is returned if `text`
+    /// contains an unsupported character, in which case nothing is written.
+    pub fn draw<T: Write>(
+        &self,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+        text: &str,
+    ) -> std::io::Result<()> {
+        for (row, line) in self.lines(text)?.iter().enumerate() {
+            screen.gotoxy(x, y + row as u32)?;
+            screen.write_all(line)?;
+        }
+        Ok(())
+    }
+
+    /// Characters of each row of `text` once rendered, e.g. to draw it into
+    /// a [`Frame`](crate::frame::Frame).
+    ///
+    /// An error of kind [`ErrorKind::InvalidInput`] is returned if `text`
+    /// contains an unsupported character.
+    pub fn lines(&self, text: &str) -> std::io::Result<Vec<Vec<u8>>> {
+        let mut lines = vec![Vec::new(); self.rows as usize];
+        for (i, c) in text.chars().enumerate() {
+            let cells = self.char_cells(c).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("character {:?} cannot be drawn with big digits", c),
+                )
+            })?;
+
+            for (line, row) in lines.iter_mut().zip(cells) {
+                if i > 0 {
+                    line.push(b' ');
+                }
+                line.extend(row);
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Row of the middle segment, and position of the segment in it.
+    fn middle(&self) -> (u32, u8) {
+        if self.rows % 2 == 1 {
+            (self.rows / 2, CENTER)
+        } else {
+            (self.rows / 2 - 1, BOTTOM)
+        }
+    }
+
+    /// Characters of each row of `c`.
+    fn char_cells(&self, c: char) -> Option<Vec<Vec<u8>>> {
+        let (middle, middle_mask) = self.middle();
+        let last = self.rows - 1;
+
+        let cells = match c {
+            '0'..='9' => {
+                let segments = DIGITS[c as usize - '0' as usize];
+                (0..self.rows)
+                    .map(|row| self.digit_row(segments, row))
+                    .collect()
+            }
+            ':' => {
+                let dots = match self.rows {
+                    3 => [0, 2],
+                    4 => [1, 2],
+                    _ => [0, 1],
+                };
+                (0..self.rows)
+                    .map(|row| {
+                        if dots.contains(&row) {
+                            vec![DOT_SLOT]
+                        } else {
+                            vec![b' ']
+                        }
+                    })
+                    .collect()
+            }
+            '.' => (0..self.rows)
+                .map(|row| {
+                    if row == last {
+                        vec![DOT_LOW_SLOT]
+                    } else {
+                        vec![b' ']
+                    }
+                })
+                .collect(),
+            '-' => (0..self.rows)
+                .map(|row| {
+                    if row == middle {
+                        vec![mask_char(middle_mask); 3]
+                    } else {
+                        vec![b' '; 3]
+                    }
+                })
+                .collect(),
+            ' ' => (0..self.rows).map(|_| vec![b' '; 3]).collect(),
+            _ => return None,
+        };
+        Some(cells)
+    }
+
+    /// Characters of a given row of a seven-segment encoded digit.
+    fn digit_row(&self, segments: u8, row: u32) -> Vec<u8> {
+        let (middle, middle_mask) = self.middle();
+        let last = self.rows - 1;
+        let on = |seg: u8| segments & seg != 0;
+
+        // horizontal segments crossing this row
+        let mut bars = 0;
+        if row == 0 && on(SEG_A) {
+            bars |= TOP;
+        }
+        if row == middle && on(SEG_G) {
+            bars |= middle_mask;
+        }
+        if row == last && on(SEG_D) {
+            bars |= BOTTOM;
+        }
+
+        // vertical segments share the middle row on odd heights
+        let shared = self.rows % 2 == 1 && row == middle;
+        let (upper, lower) = match (row.cmp(&middle), shared) {
+            (_, true) => (UPPER, LOWER),
+            (Ordering::Greater, _) => (0, FULL),
+            _ => (FULL, 0),
+        };
+        let side = |upper_seg: u8, lower_seg: u8| {
+            let mut mask = bars;
+            if on(upper_seg) {
+                mask |= upper;
+            }
+            if on(lower_seg) {
+                mask |= lower;
+            }
+            mask_char(mask)
+        };
+
+        vec![side(SEG_F, SEG_E), mask_char(bars), side(SEG_B, SEG_C)]
+    }
+}
+
+/// Width of a supported character, in columns.
+fn char_width(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' | '-' | ' ' => Some(3),
+        ':' | '.' => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(rows: u32, text: &str) -> Vec<Vec<u8>> {
+        BigDigits::new(rows).lines(text).unwrap()
+    }
+
+    #[test]
+    fn two_rows() {
+        assert_eq!(
+            render(2, "2:7"),
+            vec![
+                &b"\x03\x03\xff \x06 \x00\x00\xff"[..],
+                &b"\xff\x02\x02 \x06   \xff"[..],
+            ]
+        );
+    }
+
+    #[test]
+    fn three_rows_share_middle() {
+        assert_eq!(
+            render(3, "4"),
+            vec![&b"\xff \xff"[..], &b"\x04\x01\xff"[..], &b"  \xff"[..]]
+        );
+    }
+
+    #[test]
+    fn four_rows() {
+        assert_eq!(
+            render(4, "0."),
+            vec![
+                &b"\xff\x00\xff  "[..],
+                &b"\xff \xff  "[..],
+                &b"\xff \xff  "[..],
+                &b"\xff\x02\xff \x07"[..],
+            ]
+        );
+    }
+
+    #[test]
+    fn width() {
+        let big = BigDigits::new(2);
+        assert_eq!(big.width("12:45"), 13 + 4);
+        assert_eq!(big.width(""), 0);
+    }
+
+    #[test]
+    fn invalid_character() {
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        let err = BigDigits::new(2).draw(&mut screen, 0, 0, "1a").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,27 @@
+//! Network bridge forwarding a remote controller to `/dev/lcd`.
+//!
+//! Usage: `charlcd-bridge [ADDRESS]`, listening on `0.0.0.0:13667` by
+//! default. A controller silent for a minute is disconnected, so that
+//! another one can take over.
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use charlcd::remote::{Bridge, DEFAULT_PORT};
+use charlcd::Screen;
+
+/// Delay between two polls of the controller.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn main() -> std::io::Result<()> {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+
+    let mut bridge = Bridge::bind(&address, Screen::default()?)?;
+    loop {
+        bridge.poll()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,54 @@
+//! Display daemon sharing `/dev/lcd` between several processes.
+//!
+//! Usage: `charlcd-daemon [SOCKET] [NAME=X,Y,WIDTH,HEIGHT]...`, listening on
+//! `/run/charlcd.sock` by default. Each `NAME=X,Y,WIDTH,HEIGHT` argument
+//! defines a region that clients can ask for.
+
+use std::env;
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use charlcd::daemon::{Server, UnixServer, DEFAULT_SOCKET_PATH};
+use charlcd::Screen;
+
+/// Delay between two polls of the clients.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn main() -> std::io::Result<()> {
+    let mut screen = Screen::default()?;
+    let mut server = Server::new(screen.width()?, screen.height()?);
+    let mut socket = PathBuf::from(DEFAULT_SOCKET_PATH);
+
+    for arg in env::args().skip(1) {
+        match arg.split_once('=') {
+            Some((name, area)) => {
+                let values: Vec<u32> = area
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                let [x, y, width, height] = values[..] else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("region {:?} is not NAME=X,Y,WIDTH,HEIGHT", arg),
+                    ));
+                };
+                server = server.region(name, x, y, width, height)?;
+            }
+            None => socket = PathBuf::from(arg),
+        }
+    }
+
+    let mut daemon = UnixServer::bind(&socket, server)?;
+    screen.reinit()?;
+    screen.clear()?;
+    loop {
+        daemon.poll()?;
+        if daemon.server_mut().render(&mut screen)? {
+            screen.flush()?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,46 @@
+//! LCDproc compatible server displaying on `/dev/lcd`.
+//!
+//! Usage: `charlcd-lcdd [ADDRESS]`, listening on `127.0.0.1:13666` by
+//! default. Key presses are read from `/dev/keypad` when it is available.
+
+use std::env;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use charlcd::keypad::{KeyAction, Keypad};
+use charlcd::lcdproc::{self, TcpServer, DEFAULT_PORT};
+use charlcd::Screen;
+
+/// Delay between two polls of the clients and the keypad.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn main() -> std::io::Result<()> {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("127.0.0.1:{}", DEFAULT_PORT));
+
+    let mut screen = Screen::default()?;
+    let (width, height) = (screen.width()?, screen.height()?);
+    let mut keypad = Keypad::default().ok();
+    let mut server = TcpServer::bind(&address, width, height)?;
+
+    screen.reinit()?;
+    screen.clear()?;
+    loop {
+        server.poll()?;
+
+        if let Some(keypad) = &mut keypad {
+            while let Some(event) = keypad.try_read_event()? {
+                if event.action != KeyAction::Release {
+                    server.server_mut().key(lcdproc::key_name(&event.key));
+                }
+            }
+        }
+
+        if server.server_mut().render(&mut screen, Instant::now())? {
+            screen.flush()?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,379 @@
+//! Draw pixels on a small area of the screen, made of custom characters.
+//!
+//! A [`Canvas`] maps a block of up to 8 character cells (e.g. 4x2 cells, that
+//! is 20x16 pixels) onto the custom characters slots of the screen memory.
+//! Pixels are drawn into an internal buffer, and only the cells that changed
+//! are sent to the screen with [`Screen::custom_char`] on
+//! [`Canvas::flush`].
+//!
+//! Note that the space between two characters on the physical screen is not
+//! part of the canvas: a line crossing two cells will show a small gap.
+//!
+//! When the `embedded-graphics` feature is enabled, [`Canvas`] implements the
+//! `DrawTarget` trait so that primitives, images and fonts of the
+//! `embedded-graphics` crate can be drawn on it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::canvas::Canvas;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut canvas = Canvas::new(4, 2); // 20x16 pixels
+//!
+//!     screen.clear()?;
+//!     canvas.place(&mut screen, 0, 0)?; // print the canvas cells once
+//!
+//!     canvas.rect(0, 0, 20, 16, true);
+//!     canvas.line(0, 0, 19, 15, true);
+//!     canvas.flush(&mut screen)?; // upload the changed cells
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::Screen;
+
+/// Width of a character cell, in pixels.
+pub const CELL_WIDTH: u32 = 5;
+/// Height of a character cell, in pixels.
+pub const CELL_HEIGHT: u32 = 8;
+
+/// Number of custom characters slots available in the screen memory.
+const SLOTS: u32 = 8;
+
+/// A pixel drawing area backed by custom characters.
+pub struct Canvas {
+    cols: u32,
+    rows: u32,
+    first_slot: u8,
+    cells: Vec<[u8; 8]>,
+    dirty: Vec<bool>,
+}
+
+impl Canvas {
+    /// Create a new blank canvas of `cols` x `rows` character cells, using
+    /// the custom characters slots starting from `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvas needs more than 8 cells.
+    pub fn new(cols: u32, rows: u32) -> Canvas {
+        let count = cols * rows;
+        assert!(
+            count <= SLOTS,
+            "a canvas cannot be made of more than {} cells",
+            SLOTS
+        );
+
+        Canvas {
+            cols,
+            rows,
+            first_slot: 0,
+            cells: vec![[0; 8]; count as usize],
+            dirty: vec![true; count as usize],
+        }
+    }
+
+    /// Use the custom characters slots starting from `slot` instead of `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the canvas cells do not fit in the remaining slots.
+    pub fn first_slot(mut self, slot: u8) -> Canvas {
+        assert!(
+            slot as u32 + self.cols * self.rows <= SLOTS,
+            "canvas cells do not fit in slots {} to {}",
+            slot,
+            SLOTS - 1
+        );
+        self.first_slot = slot;
+        self
+    }
+
+    /// Width of the canvas, in pixels.
+    pub fn width(&self) -> u32 {
+        self.cols * CELL_WIDTH
+    }
+
+    /// Height of the canvas, in pixels.
+    pub fn height(&self) -> u32 {
+        self.rows * CELL_HEIGHT
+    }
+
+    /// Turn all the pixels off.
+    pub fn clear(&mut self) {
+        for (cell, dirty) in self.cells.iter_mut().zip(self.dirty.iter_mut()) {
+            if *cell != [0; 8] {
+                *cell = [0; 8];
+                *dirty = true;
+            }
+        }
+    }
+
+    /// State of the pixel at (`x`, `y`), `false` if out of the canvas.
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        match self.locate(x, y) {
+            Some((cell, row, bit)) => self.cells[cell][row] & bit != 0,
+            None => false,
+        }
+    }
+
+    /// Set the state of the pixel at (`x`, `y`).
+    ///
+    /// Pixels out of the canvas are silently ignored.
+    pub fn set_pixel(&mut self, x: i32, y: i32, on: bool) {
+        if let Some((cell, row, bit)) = self.locate(x, y) {
+            let old = self.cells[cell][row];
+            let new = if on { old | bit } else { old & !bit };
+            if new != old {
+                self.cells[cell][row] = new;
+                self.dirty[cell] = true;
+            }
+        }
+    }
+
+    /// Draw a line from (`x0`, `y0`) to (`x1`, `y1`), both ends included.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, on: bool) {
+        self.line_i64(x0 as i64, y0 as i64, x1 as i64, y1 as i64, on);
+    }
+
+    /// Draw a line stepping along its longest axis, only over the part of
+    /// that axis on the canvas, so that any coordinates can be given.
+    fn line_i64(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, on: bool) {
+        if (x1 - x0).abs() >= (y1 - y0).abs() {
+            for (x, y) in steps(x0, y0, x1, y1, self.width()) {
+                self.set_pixel_i64(x, y, on);
+            }
+        } else {
+            for (y, x) in steps(y0, x0, y1, x1, self.height()) {
+                self.set_pixel_i64(x, y, on);
+            }
+        }
+    }
+
+    fn set_pixel_i64(&mut self, x: i64, y: i64, on: bool) {
+        if let (Ok(x), Ok(y)) = (i32::try_from(x), i32::try_from(y)) {
+            self.set_pixel(x, y, on);
+        }
+    }
+
+    /// Draw the outline of a `width` x `height` rectangle whose top-left
+    /// corner is (`x`, `y`).
+    pub fn rect(&mut self, x: i32, y: i32, width: u32, height: u32, on: bool) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (x, y) = (x as i64, y as i64);
+        let (x1, y1) = (x + width as i64 - 1, y + height as i64 - 1);
+        self.line_i64(x, y, x1, y, on);
+        self.line_i64(x, y1, x1, y1, on);
+        self.line_i64(x, y, x, y1, on);
+        self.line_i64(x1, y, x1, y1, on);
+    }
+
+    /// Fill a `width` x `height` rectangle whose top-left corner is
+    /// (`x`, `y`).
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, on: bool) {
+        // only the part of the rectangle on the canvas
+        let x_end = x.saturating_add(width.min(i32::MAX as u32) as i32);
+        let y_end = y.saturating_add(height.min(i32::MAX as u32) as i32);
+        for py in y.max(0)..y_end.min(self.height() as i32) {
+            for px in x.max(0)..x_end.min(self.width() as i32) {
+                self.set_pixel(px, py, on);
+            }
+        }
+    }
+
+    /// Print the characters of the canvas cells with the top-left corner at
+    /// the (`x`, `y`) position.
+    ///
+    /// This only has to be done once: later changes are displayed by
+    /// [`Canvas::flush`], as the screen shows the new custom characters
+    /// wherever they are printed.
+    pub fn place<T: Write>(&self, screen: &mut Screen<T>, x: u32, y: u32) -> std::io::Result<()> {
+        for row in 0..self.rows {
+            let codes: Vec<u8> = (0..self.cols)
+                .map(|col| self.first_slot + (row * self.cols + col) as u8)
+                .collect();
+            screen.gotoxy(x, y + row)?;
+            screen.write_all(&codes)?;
+        }
+        Ok(())
+    }
+
+    /// Upload the cells that changed since the last flush into the screen
+    /// memory.
+    pub fn flush<T: Write>(&mut self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        for (i, (cell, dirty)) in self.cells.iter().zip(self.dirty.iter_mut()).enumerate() {
+            if *dirty {
+                screen.custom_char(self.first_slot + i as u8, *cell)?;
+                *dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the cell, row in the cell and bit mask of a pixel.
+    fn locate(&self, x: i32, y: i32) -> Option<(usize, usize, u8)> {
+        if x < 0 || y < 0 || x as u32 >= self.width() || y as u32 >= self.height() {
+            return None;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let cell = (y / CELL_HEIGHT) * self.cols + x / CELL_WIDTH;
+        let bit = 1 << (CELL_WIDTH - 1 - x % CELL_WIDTH);
+        Some((cell as usize, (y % CELL_HEIGHT) as usize, bit))
+    }
+}
+
+/// Points of the line from (`a0`, `b0`) to (`a1`, `b1`), one for each value
+/// of `a` from `0` to `len - 1` that the line covers, `b` being rounded to
+/// the nearest integer.
+fn steps(a0: i64, b0: i64, a1: i64, b1: i64, len: u32) -> impl Iterator<Item = (i64, i64)> {
+    // always walk in the same direction, so that both ends give the same line
+    let (a0, b0, a1, b1) = if a0 <= a1 {
+        (a0, b0, a1, b1)
+    } else {
+        (a1, b1, a0, b0)
+    };
+    let (da, db) = ((a1 - a0) as i128, (b1 - b0) as i128);
+    (a0.max(0)..=a1.min(len as i64 - 1)).map(move |a| {
+        if da == 0 {
+            return (a, b0);
+        }
+        let offset = (2 * (a - a0) as i128 * db + da).div_euclid(2 * da);
+        (a, b0 + offset as i64)
+    })
+}
+
+#[cfg(feature = "embedded-graphics")]
+mod graphics {
+    use super::Canvas;
+
+    use embedded_graphics_core::pixelcolor::BinaryColor;
+    use embedded_graphics_core::prelude::*;
+
+    impl OriginDimensions for Canvas {
+        fn size(&self) -> Size {
+            Size::new(self.width(), self.height())
+        }
+    }
+
+    impl DrawTarget for Canvas {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                self.set_pixel(point.x, point.y, color.is_on());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_map_to_cells() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, true);
+        canvas.set_pixel(9, 15, true);
+        canvas.set_pixel(10, 0, true); // out of the canvas
+
+        assert_eq!(canvas.cells[0][0], 0b10000);
+        assert_eq!(canvas.cells[3][7], 0b00001);
+        assert!(canvas.pixel(9, 15));
+        assert!(!canvas.pixel(10, 0));
+    }
+
+    #[test]
+    fn line_and_rect() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.line(0, 0, 4, 4, true);
+        assert_eq!(
+            canvas.cells[0],
+            [0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0, 0, 0]
+        );
+
+        canvas.clear();
+        canvas.rect(0, 0, 5, 8, true);
+        assert_eq!(
+            canvas.cells[0],
+            [0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111]
+        );
+
+        // filled up to the edges of the canvas, without overflowing
+        canvas.clear();
+        canvas.fill_rect(-100, 6, u32::MAX, u32::MAX, true);
+        canvas.fill_rect(i32::MAX, 0, u32::MAX, 1, true);
+        assert_eq!(canvas.cells[0], [0, 0, 0, 0, 0, 0, 0b11111, 0b11111]);
+    }
+
+    #[test]
+    fn extreme_coordinates() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.rect(i32::MAX, 0, 2, 2, true);
+        canvas.rect(i32::MIN, i32::MIN, u32::MAX, u32::MAX, true);
+        canvas.line(i32::MIN, i32::MIN, i32::MAX, i32::MIN, true);
+        assert_eq!(canvas.cells[0], [0; 8]);
+
+        // only the part of the line on the canvas is drawn
+        canvas.line(-1_000_000_000, 3, 1_000_000_000, 3, true);
+        canvas.rect(-10, -10, 12, u32::MAX, true);
+        canvas.line(2, i32::MAX, 2, i32::MIN, true);
+        assert_eq!(
+            canvas.cells[0],
+            [0b01100, 0b01100, 0b01100, 0b11111, 0b01100, 0b01100, 0b01100, 0b01100]
+        );
+    }
+
+    #[test]
+    fn flush_only_changed_cells() {
+        let mut canvas = Canvas::new(2, 1).first_slot(6);
+        let mut buf = Vec::new();
+        canvas.flush(&mut Screen::new(&mut buf)).unwrap();
+        assert_eq!(buf.len(), 2 * b"\x1b[LG60000000000000000;".len());
+
+        canvas.set_pixel(5, 7, true);
+        let mut buf = Vec::new();
+        canvas.flush(&mut Screen::new(&mut buf)).unwrap();
+        assert_eq!(buf, b"\x1b[LG70000000000000010;");
+    }
+
+    #[cfg(feature = "embedded-graphics")]
+    #[test]
+    fn draw_target() {
+        use embedded_graphics_core::pixelcolor::BinaryColor;
+        use embedded_graphics_core::prelude::*;
+
+        let mut canvas = Canvas::new(4, 2);
+        assert_eq!(canvas.size(), Size::new(20, 16));
+
+        canvas
+            .draw_iter([
+                Pixel(Point::new(19, 15), BinaryColor::On),
+                Pixel(Point::new(-1, 3), BinaryColor::On),
+            ])
+            .unwrap();
+        assert!(canvas.pixel(19, 15));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_cells() {
+        Canvas::new(3, 3);
+    }
+}
@@ -0,0 +1,257 @@
+//! Rotate through pages on a timer.
+//!
+//! A [`Carousel`] holds several pages, each of them shown for its own
+//! interval before switching to the next one. Pages are drawn into a
+//! [`Frame`] by anything that implements the [`Page`] trait, including
+//! closures, and the carousel renders the frame on the screen with a page
+//! indicator in the bottom-right corner.
+//!
+//! The pages can also be switched manually with navigation events, which
+//! restarts the interval of the page displayed.
+//!
+//! Only the characters that changed since the previous update are written to
+//! the screen, see [`Carousel::invalidate`] to draw it again entirely.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::thread;
+//! use std::time::{Duration, Instant};
+//! use charlcd::Screen;
+//! use charlcd::carousel::Carousel;
+//! use charlcd::frame::Frame;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!
+//!     let mut carousel = Carousel::new(screen.width()?, screen.height()?)
+//!         .page(Duration::from_secs(5), |frame: &mut Frame| {
+//!             frame.print(0, 0, b"Temp 21.5");
+//!         })
+//!         .page(Duration::from_secs(2), |frame: &mut Frame| {
+//!             frame.print(0, 0, b"Load 0.42");
+//!         });
+//!
+//!     loop {
+//!         carousel.update(&mut screen, Instant::now())?;
+//!         screen.flush()?;
+//!         thread::sleep(Duration::from_millis(100));
+//!     }
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::time::{Duration, Instant};
+
+use crate::frame::Frame;
+use crate::input::NavEvent;
+use crate::Screen;
+
+/// Something that draws a page of a [`Carousel`].
+pub trait Page {
+    /// Draw the page into `frame`, that is blank when this is called.
+    fn draw(&mut self, frame: &mut Frame);
+}
+
+impl<F> Page for F
+where
+    F: FnMut(&mut Frame),
+{
+    fn draw(&mut self, frame: &mut Frame) {
+        self(frame)
+    }
+}
+
+/// Pages shown one after the other.
+pub struct Carousel {
+    pages: Vec<(Box<dyn Page>, Duration)>,
+    current: usize,
+    // when the current page was displayed, None to restart its interval at
+    // the next update
+    shown_at: Option<Instant>,
+    indicator: bool,
+    frame: Frame,
+    // what the screen shows, `None` to draw the whole frame at the next
+    // update
+    displayed: Option<Frame>,
+}
+
+impl Carousel {
+    /// Create a new carousel without any page, taking `width` x `height`
+    /// characters.
+    pub fn new(width: u32, height: u32) -> Carousel {
+        Carousel {
+            pages: Vec::new(),
+            current: 0,
+            shown_at: None,
+            indicator: true,
+            frame: Frame::new(width, height),
+            displayed: None,
+        }
+    }
+
+    /// Add a page displayed for `interval` before switching to the next one.
+    pub fn page<P: Page + 'static>(mut self, interval: Duration, page: P) -> Carousel {
+        self.pages.push((Box::new(page), interval));
+        self
+    }
+
+    /// Show or hide the page indicator (e.g. `2/3`), shown by default when
+    /// there is more than one page.
+    pub fn indicator(mut self, indicator: bool) -> Carousel {
+        self.indicator = indicator;
+        self
+    }
+
+    /// Number of pages.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Index of the page displayed.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Switch to the next page, after the last one comes the first one.
+    pub fn next_page(&mut self) {
+        self.switch(1);
+    }
+
+    /// Switch to the previous page, before the first one comes the last one.
+    pub fn prev_page(&mut self) {
+        self.switch(-1);
+    }
+
+    /// Switch pages according to a navigation event: left and up (or a
+    /// counterclockwise rotation) show the previous page, right and down (or
+    /// a clockwise rotation) the next one.
+    ///
+    /// Returns whether the event was used.
+    pub fn handle(&mut self, event: NavEvent) -> bool {
+        match event {
+            NavEvent::Left | NavEvent::Up => self.switch(-1),
+            NavEvent::Right | NavEvent::Down => self.switch(1),
+            NavEvent::Rotate(steps) => self.switch(steps as i64),
+            NavEvent::Select | NavEvent::Back => return false,
+        }
+        true
+    }
+
+    /// Draw the whole page at the next update, e.g. after the screen was
+    /// changed outside of the carousel.
+    pub fn invalidate(&mut self) {
+        self.displayed = None;
+    }
+
+    /// Switch to the next page if the interval of the current one is over at
+    /// `now`, then draw the current page at the top-left corner of the
+    /// screen, writing only what changed since the previous update.
+    pub fn update<T: Write>(&mut self, screen: &mut Screen<T>, now: Instant) -> Result<()> {
+        if self.pages.is_empty() {
+            return Ok(());
+        }
+
+        let shown_at = *self.shown_at.get_or_insert(now);
+        if now.saturating_duration_since(shown_at) >= self.pages[self.current].1 {
+            self.current = (self.current + 1) % self.pages.len();
+            self.shown_at = Some(now);
+        }
+
+        self.frame.clear();
+        self.pages[self.current].0.draw(&mut self.frame);
+        if self.indicator && self.pages.len() > 1 {
+            let indicator = format!("{}/{}", self.current + 1, self.pages.len());
+            let x = self.frame.width().saturating_sub(indicator.len() as u32);
+            let y = self.frame.height().saturating_sub(1);
+            self.frame.print(x, y, indicator);
+        }
+        match &self.displayed {
+            Some(displayed) => {
+                self.frame.render_diff(displayed, screen, 0, 0)?;
+            }
+            None => self.frame.render(screen, 0, 0)?,
+        }
+        self.displayed = Some(self.frame.clone());
+        Ok(())
+    }
+
+    fn switch(&mut self, steps: i64) {
+        if self.pages.is_empty() {
+            return;
+        }
+        let count = self.pages.len() as i64;
+        self.current = (self.current as i64 + steps).rem_euclid(count) as usize;
+        self.shown_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carousel() -> Carousel {
+        Carousel::new(8, 1)
+            .page(Duration::from_secs(2), |frame: &mut Frame| {
+                frame.print(0, 0, b"one");
+            })
+            .page(Duration::from_secs(1), |frame: &mut Frame| {
+                frame.print(0, 0, b"two");
+            })
+    }
+
+    fn update(carousel: &mut Carousel, now: Instant) -> Vec<u8> {
+        let mut buf = Vec::new();
+        carousel.update(&mut Screen::new(&mut buf), now).unwrap();
+        buf
+    }
+
+    #[test]
+    fn rotate_on_timer() {
+        let mut carousel = carousel();
+        let start = Instant::now();
+
+        assert_eq!(update(&mut carousel, start), b"\x1b[Lx0y0;one  1/2");
+        update(&mut carousel, start + Duration::from_secs(1));
+        assert_eq!(carousel.current(), 0);
+        assert_eq!(
+            update(&mut carousel, start + Duration::from_secs(2)),
+            b"\x1b[Lx0y0;two  2"
+        );
+        update(&mut carousel, start + Duration::from_secs(3));
+        assert_eq!(carousel.current(), 0);
+    }
+
+    #[test]
+    fn manual_switch() {
+        let mut carousel = carousel().indicator(false);
+        let start = Instant::now();
+        update(&mut carousel, start);
+
+        assert!(carousel.handle(NavEvent::Rotate(-1)));
+        assert!(!carousel.handle(NavEvent::Select));
+        assert_eq!(
+            update(&mut carousel, start + Duration::from_secs(5)),
+            b"\x1b[Lx0y0;two"
+        );
+
+        // the interval restarted when the page was switched
+        update(&mut carousel, start + Duration::from_millis(5900));
+        assert_eq!(carousel.current(), 1);
+        update(&mut carousel, start + Duration::from_secs(6));
+        assert_eq!(carousel.current(), 0);
+
+        // nothing changed, nothing written until invalidated
+        assert_eq!(update(&mut carousel, start + Duration::from_secs(6)), b"");
+        carousel.invalidate();
+        assert_eq!(
+            update(&mut carousel, start + Duration::from_secs(6)),
+            b"\x1b[Lx0y0;one     "
+        );
+    }
+}
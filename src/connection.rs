@@ -0,0 +1,128 @@
+//! Non-blocking line-based connections of the servers.
+
+use std::io::{ErrorKind, Read, Write};
+
+/// Longest line accepted from a client, line ending included. A client
+/// sending a longer line is disconnected.
+pub(crate) const MAX_LINE_LEN: usize = 4096;
+
+/// A client connection exchanging lines of text.
+pub(crate) struct Connection<S> {
+    pub stream: S,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    // the client is done, close once the output is sent
+    pub closing: bool,
+    closed: bool,
+}
+
+impl<S> Connection<S>
+where
+    S: Read + Write,
+{
+    /// Wrap a stream already set in non-blocking mode.
+    pub fn new(stream: S) -> Connection<S> {
+        Connection {
+            stream,
+            input: Vec::new(),
+            output: Vec::new(),
+            closing: false,
+            closed: false,
+        }
+    }
+
+    /// Whether the connection should be dropped: it failed, was closed by
+    /// the client, or is closing with all of its output sent.
+    pub fn is_done(&self) -> bool {
+        self.closed || (self.closing && self.output.is_empty())
+    }
+
+    /// Read the available bytes, returning the complete lines received,
+    /// without their line ending.
+    pub fn read_lines(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut buf = [0u8; 512];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    self.input.extend_from_slice(&buf[..count]);
+                    self.split_lines(&mut lines);
+                    // a pending line this long cannot end in time
+                    if self.input.len() >= MAX_LINE_LEN {
+                        self.input.clear();
+                        self.closed = true;
+                    }
+                    if self.closed {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Move the complete lines of the input to `lines`, dropping the
+    /// connection if one of them is too long.
+    fn split_lines(&mut self, lines: &mut Vec<String>) {
+        while let Some(end) = self.input.iter().position(|&b| b == b'\n') {
+            if end >= MAX_LINE_LEN {
+                self.input.clear();
+                self.closed = true;
+                return;
+            }
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            lines.push(line.trim_end_matches(&['\n', '\r'][..]).to_string());
+        }
+    }
+
+    /// Queue `data` and send as much of the pending output as possible.
+    pub fn send(&mut self, data: &[u8]) {
+        self.output.extend_from_slice(data);
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    self.output.drain(..count);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn drop_long_lines() {
+        let mut input = b"ok\r\n".to_vec();
+        input.resize(input.len() + MAX_LINE_LEN, b'a');
+        input.push(b'\n');
+        let mut connection = Connection::new(Cursor::new(input));
+
+        assert_eq!(connection.read_lines(), ["ok"]);
+        assert!(connection.is_done());
+        assert!(connection.input.is_empty());
+    }
+}
@@ -0,0 +1,751 @@
+//! Share the display between several processes through a daemon.
+//!
+//! `/dev/lcd` can be opened by several processes at once, but the escape
+//! sequences they write interleave and corrupt each other. A [`Server`] owns
+//! the display and composes what its clients draw instead: each client gets
+//! either a named region of the display or the whole display, with a
+//! priority. Where areas overlap, the client with the highest priority is
+//! displayed, the last connected one on equal priorities.
+//!
+//! The clients talk to the server over a Unix socket with one JSON
+//! [`Request`] per line, and get a JSON [`Reply`] once connected. The
+//! [`Client`] writer hides this protocol, so that applications keep using a
+//! [`Screen`], see [`Screen::from_daemon`].
+//!
+//! Every client draws as if it had a display of the size of its area to
+//! itself. Custom characters are shared: a slot shows the glyph of the client
+//! with the highest priority that defined it. The cursor, display and
+//! backlight flags are those of the client with the highest priority, and
+//! display shifts are ignored.
+//!
+//! This module requires the `daemon` feature. The `charlcd-daemon` binary
+//! runs a [`UnixServer`] on `/dev/lcd`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::daemon::{Slot, DEFAULT_SOCKET_PATH};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     // draw in the "status" region of the daemon, over the other clients
+//!     let slot = Slot::new("backup").region("status").priority(10);
+//!     let mut screen = Screen::from_daemon(DEFAULT_SOCKET_PATH, slot)?;
+//!
+//!     screen.clear()?;
+//!     screen.write(b"Backup running")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::fs;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::mem;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::connection::Connection;
+use crate::frame::Frame;
+use crate::protocol::{Command, Parser};
+use crate::shadow::{Flags, Snapshot};
+use crate::Screen;
+
+/// Socket the daemon listens on by default.
+pub const DEFAULT_SOCKET_PATH: &str = "/run/charlcd.sock";
+
+/// Largest number of bytes sent in a single write request, whose JSON
+/// encoding fits in the line length accepted by the server.
+const WRITE_CHUNK_LEN: usize = 512;
+
+/// Number of custom character slots.
+const SLOTS: u8 = 8;
+
+/// Flags of the display when no client is connected.
+const IDLE_FLAGS: Flags = Flags {
+    display: true,
+    cursor: false,
+    blink: false,
+    backlight: true,
+};
+
+/// Where and how a client draws, sent when it connects.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slot {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
+    #[serde(default)]
+    priority: u8,
+}
+
+impl Slot {
+    /// Create a new slot for the client `name`, unique among the connected
+    /// clients, taking the whole display with the lowest priority.
+    pub fn new(name: &str) -> Slot {
+        Slot {
+            name: name.to_string(),
+            region: None,
+            priority: 0,
+        }
+    }
+
+    /// Draw in the region `name` of the daemon instead of the whole display.
+    pub fn region(mut self, name: &str) -> Slot {
+        self.region = Some(name.to_string());
+        self
+    }
+
+    /// Show over the clients of lower priority.
+    pub fn priority(mut self, priority: u8) -> Slot {
+        self.priority = priority;
+        self
+    }
+}
+
+/// A line sent by a client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// Take a slot, first thing after connecting.
+    Open(Slot),
+    /// Bytes written to the display, escape sequences included.
+    Write(Vec<u8>),
+}
+
+/// A line sent by the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    /// The slot was opened, giving the size of its area.
+    Ready { width: u32, height: u32 },
+    /// The request was rejected, the server closes the connection.
+    Error(String),
+}
+
+/// Identifier of a client connected to a [`Server`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+/// A rectangle of the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Area {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// The slot of a client, and what it drew in it.
+struct Opened {
+    name: String,
+    area: Area,
+    priority: u8,
+    order: u64,
+    parser: Parser,
+    snapshot: Snapshot,
+}
+
+struct Peer {
+    id: ClientId,
+    opened: Option<Opened>,
+    output: String,
+}
+
+/// State of a display daemon: the regions of the display, the clients and
+/// what is displayed.
+pub struct Server {
+    width: u32,
+    height: u32,
+    regions: Vec<(String, Area)>,
+    peers: Vec<Peer>,
+    next_client: u64,
+    next_order: u64,
+    // what was last sent to the screen, None before the first render
+    displayed: Option<Frame>,
+    loaded: [Option<[u8; 8]>; SLOTS as usize],
+    flags: Option<Flags>,
+    cursor: Option<(u32, u32)>,
+    flash: bool,
+}
+
+impl Server {
+    /// Create a new server for a `width` x `height` display, without any
+    /// region.
+    pub fn new(width: u32, height: u32) -> Server {
+        Server {
+            width,
+            height,
+            regions: Vec::new(),
+            peers: Vec::new(),
+            next_client: 0,
+            next_order: 0,
+            displayed: None,
+            loaded: [None; SLOTS as usize],
+            flags: None,
+            cursor: None,
+            flash: false,
+        }
+    }
+
+    /// Define the region `name`, a `width` x `height` area with its top-left
+    /// corner at the (`x`, `y`) position of the display.
+    ///
+    /// An error of kind [`ErrorKind::InvalidInput`] is returned if the area
+    /// does not fit in the display.
+    pub fn region(mut self, name: &str, x: u32, y: u32, width: u32, height: u32) -> Result<Server> {
+        let fits =
+            |start: u32, len: u32, size: u32| start.checked_add(len).is_some_and(|end| end <= size);
+        if !fits(x, width, self.width) || !fits(y, height, self.height) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "region {:?} does not fit in a {}x{} display",
+                    name, self.width, self.height
+                ),
+            ));
+        }
+        self.regions.retain(|(n, _)| n != name);
+        let area = Area {
+            x,
+            y,
+            width,
+            height,
+        };
+        self.regions.push((name.to_string(), area));
+        Ok(self)
+    }
+
+    /// Register a new client, that has to open a slot first.
+    pub fn connect(&mut self) -> ClientId {
+        let id = ClientId(self.next_client);
+        self.next_client += 1;
+        self.peers.push(Peer {
+            id,
+            opened: None,
+            output: String::new(),
+        });
+        id
+    }
+
+    /// Forget a client and what it drew.
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.peers.retain(|p| p.id != client);
+    }
+
+    /// Names of the clients that opened a slot.
+    pub fn clients(&self) -> Vec<&str> {
+        self.peers
+            .iter()
+            .filter_map(|p| p.opened.as_ref())
+            .map(|o| o.name.as_str())
+            .collect()
+    }
+
+    /// Handle a JSON request line sent by `client`.
+    ///
+    /// The reply, if any, is queued in the output of the client, see
+    /// [`take_output`](Server::take_output). Returns `false` if the request
+    /// was rejected, in which case the connection should be closed once the
+    /// error is sent.
+    pub fn request(&mut self, client: ClientId, line: &str) -> bool {
+        match self.handle(client, line) {
+            Ok(None) => true,
+            Ok(Some(reply)) => {
+                self.send(client, &reply);
+                true
+            }
+            Err(message) => {
+                self.send(client, &Reply::Error(message));
+                false
+            }
+        }
+    }
+
+    /// Take the lines waiting to be sent to `client`.
+    pub fn take_output(&mut self, client: ClientId) -> String {
+        match self.peers.iter_mut().find(|p| p.id == client) {
+            Some(p) => mem::take(&mut p.output),
+            None => String::new(),
+        }
+    }
+
+    /// Compose what the clients drew and send what changed to `screen`.
+    ///
+    /// Returns whether something was written to the screen.
+    pub fn render<T: Write>(&mut self, screen: &mut Screen<T>) -> Result<bool> {
+        let mut opened: Vec<&Opened> = self
+            .peers
+            .iter()
+            .filter_map(|p| p.opened.as_ref())
+            .collect();
+        opened.sort_by_key(|o| (o.priority, o.order));
+
+        let mut frame = Frame::new(self.width, self.height);
+        for o in &opened {
+            for row in 0..o.area.height {
+                frame.print(o.area.x, o.area.y + row, o.snapshot.line(row));
+            }
+        }
+
+        let mut written = false;
+        for slot in 0..SLOTS {
+            let glyph = opened.iter().rev().find_map(|o| o.snapshot.glyph(slot));
+            if let Some(glyph) = glyph {
+                if self.loaded[slot as usize] != Some(glyph) {
+                    screen.custom_char(slot, glyph)?;
+                    self.loaded[slot as usize] = Some(glyph);
+                    written = true;
+                }
+            }
+        }
+
+        match &self.displayed {
+            Some(displayed) => written |= frame.render_diff(displayed, screen, 0, 0)? > 0,
+            None => {
+                frame.render(screen, 0, 0)?;
+                written = true;
+            }
+        }
+        self.displayed = Some(frame);
+
+        let (flags, cursor) = match opened.last() {
+            Some(top) => {
+                let (x, y) = top.snapshot.cursor();
+                (top.snapshot.flags(), (top.area.x + x, top.area.y + y))
+            }
+            None => (IDLE_FLAGS, (0, 0)),
+        };
+        written |= self.apply_flags(screen, flags)?;
+        if mem::take(&mut self.flash) {
+            screen.flash_backlight()?;
+            written = true;
+        }
+        // writing moved the cursor of the display
+        if (flags.cursor || flags.blink) && (written || self.cursor != Some(cursor)) {
+            screen.gotoxy(cursor.0, cursor.1)?;
+            written = true;
+        }
+        self.cursor = Some(cursor);
+        Ok(written)
+    }
+
+    fn handle(
+        &mut self,
+        client: ClientId,
+        line: &str,
+    ) -> std::result::Result<Option<Reply>, String> {
+        let request: Request =
+            serde_json::from_str(line).map_err(|e| format!("invalid request: {}", e))?;
+
+        match request {
+            Request::Open(slot) => {
+                if self.clients().contains(&slot.name.as_str()) {
+                    return Err(format!("name {:?} already in use", slot.name));
+                }
+                let area = match &slot.region {
+                    Some(region) => self
+                        .regions
+                        .iter()
+                        .find(|(name, _)| name == region)
+                        .map(|(_, area)| *area)
+                        .ok_or_else(|| format!("unknown region {:?}", region))?,
+                    None => Area {
+                        x: 0,
+                        y: 0,
+                        width: self.width,
+                        height: self.height,
+                    },
+                };
+                let order = self.next_order;
+                let peer = self.peer_mut(client)?;
+                if peer.opened.is_some() {
+                    return Err("slot already opened".to_string());
+                }
+                peer.opened = Some(Opened {
+                    name: slot.name,
+                    area,
+                    priority: slot.priority,
+                    order,
+                    parser: Parser::new(),
+                    snapshot: Snapshot::new(area.width, area.height),
+                });
+                self.next_order += 1;
+                Ok(Some(Reply::Ready {
+                    width: area.width,
+                    height: area.height,
+                }))
+            }
+            Request::Write(data) => {
+                let opened = self
+                    .peer_mut(client)?
+                    .opened
+                    .as_mut()
+                    .ok_or_else(|| "no slot opened".to_string())?;
+                let mut flash = false;
+                for command in opened.parser.parse(&data) {
+                    flash |= command == Command::FlashBacklight;
+                    opened.snapshot.apply(&command);
+                }
+                self.flash |= flash;
+                Ok(None)
+            }
+        }
+    }
+
+    fn peer_mut(&mut self, client: ClientId) -> std::result::Result<&mut Peer, String> {
+        self.peers
+            .iter_mut()
+            .find(|p| p.id == client)
+            .ok_or_else(|| "unknown client".to_string())
+    }
+
+    fn send(&mut self, client: ClientId, reply: &Reply) {
+        if let Some(p) = self.peers.iter_mut().find(|p| p.id == client) {
+            let line = serde_json::to_string(reply).expect("replies can be serialized");
+            p.output.push_str(&line);
+            p.output.push('\n');
+        }
+    }
+
+    /// Send the flags that changed, returning whether something was written.
+    fn apply_flags<T: Write>(&mut self, screen: &mut Screen<T>, flags: Flags) -> Result<bool> {
+        let previous = self.flags.replace(flags);
+        flags.write_diff(previous, screen)
+    }
+}
+
+/// A [`Server`] listening for clients on a Unix socket.
+///
+/// Nothing blocks: [`poll`](UnixServer::poll) accepts the new clients and
+/// handles what was received, and has to be called regularly along with
+/// [`Server::render`]. The socket file is removed when the server is
+/// dropped.
+pub struct UnixServer {
+    listener: UnixListener,
+    path: PathBuf,
+    server: Server,
+    connections: Vec<(ClientId, Connection<UnixStream>)>,
+}
+
+impl UnixServer {
+    /// Listen on the socket `path` for the clients of `server`.
+    ///
+    /// A socket file left by a daemon that is not running anymore is
+    /// replaced, an error of kind [`ErrorKind::AddrInUse`] is returned if a
+    /// daemon is still listening on it or if `path` is not a socket.
+    pub fn bind<P: AsRef<Path>>(path: P, server: Server) -> Result<UnixServer> {
+        let path = path.as_ref();
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("a daemon is already listening on {}", path.display()),
+                ));
+            }
+            // only replace a stale socket, never another kind of file
+            if !fs::symlink_metadata(path)?.file_type().is_socket() {
+                return Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(UnixServer {
+            listener,
+            path: path.to_path_buf(),
+            server,
+            connections: Vec::new(),
+        })
+    }
+
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    pub fn server_mut(&mut self) -> &mut Server {
+        &mut self.server
+    }
+
+    /// Number of connected clients.
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Accept the pending connections, handle the requests received and
+    /// send the pending replies.
+    ///
+    /// Errors of a connection only close it, only errors of the listening
+    /// socket are returned.
+    pub fn poll(&mut self) -> Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    let client = self.server.connect();
+                    self.connections.push((client, Connection::new(stream)));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        for (client, connection) in &mut self.connections {
+            if !connection.closing {
+                for line in connection.read_lines() {
+                    if !self.server.request(*client, &line) {
+                        connection.closing = true;
+                        break;
+                    }
+                }
+            }
+            connection.send(self.server.take_output(*client).as_bytes());
+        }
+
+        let server = &mut self.server;
+        self.connections.retain(|(client, connection)| {
+            if connection.is_done() {
+                server.disconnect(*client);
+            }
+            !connection.is_done()
+        });
+        Ok(())
+    }
+}
+
+impl Drop for UnixServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A writer sending what is written to it to a display daemon.
+///
+/// Writes are buffered until the writer is flushed, so that the escape
+/// sequences of a whole update reach the daemon at once.
+pub struct Client {
+    stream: UnixStream,
+    width: u32,
+    height: u32,
+    buffer: Vec<u8>,
+}
+
+impl Client {
+    /// Connect to the daemon listening on the socket `path`, and open `slot`.
+    ///
+    /// An error of kind [`ErrorKind::ConnectionRefused`] is returned if the
+    /// daemon rejects the slot, e.g. because its region does not exist.
+    pub fn connect<P: AsRef<Path>>(path: P, slot: Slot) -> Result<Client> {
+        let mut stream = UnixStream::connect(path)?;
+        send_request(&mut stream, &Request::Open(slot))?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        match serde_json::from_str(&line) {
+            Ok(Reply::Ready { width, height }) => Ok(Client {
+                stream,
+                width,
+                height,
+                buffer: Vec::new(),
+            }),
+            Ok(Reply::Error(message)) => Err(Error::new(ErrorKind::ConnectionRefused, message)),
+            Err(e) => Err(Error::new(ErrorKind::InvalidData, e)),
+        }
+    }
+
+    /// Width of the area of the client, in characters.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the area of the client, in characters.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // keep each request within the line length accepted by the server
+        for data in mem::take(&mut self.buffer).chunks(WRITE_CHUNK_LEN) {
+            send_request(&mut self.stream, &Request::Write(data.to_vec()))?;
+        }
+        self.stream.flush()
+    }
+}
+
+fn send_request(stream: &mut UnixStream, request: &Request) -> Result<()> {
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+impl Screen<Client> {
+    /// Create a Screen instance drawing in `slot` through the daemon
+    /// listening on the socket `path`.
+    pub fn from_daemon<P: AsRef<Path>>(path: P, slot: Slot) -> Result<Screen<Client>> {
+        Ok(Screen::new(Client::connect(path, slot)?))
+    }
+
+    /// Get the width of the area of the client, in number of characters it
+    /// can display.
+    pub fn width(&self) -> Result<u32> {
+        Ok(self.get_ref().width())
+    }
+
+    /// Get the height of the area of the client, in number of characters it
+    /// can display.
+    pub fn height(&self) -> Result<u32> {
+        Ok(self.get_ref().height())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::shadow::Shadow;
+
+    fn write(server: &mut Server, client: ClientId, data: &[u8]) {
+        let line = serde_json::to_string(&Request::Write(data.to_vec())).unwrap();
+        assert!(server.request(client, &line));
+    }
+
+    fn display() -> Screen<Shadow<Vec<u8>>> {
+        Screen::new(Vec::new()).shadowed(8, 2)
+    }
+
+    #[test]
+    fn compose_by_priority() {
+        let mut server = Server::new(8, 2).region("status", 0, 1, 8, 1).unwrap();
+        let (a, b) = (server.connect(), server.connect());
+        assert!(server.request(a, r#"{"open":{"name":"a"}}"#));
+        assert!(server.request(b, r#"{"open":{"name":"b","region":"status","priority":1}}"#));
+        assert_eq!(
+            server.take_output(a),
+            "{\"ready\":{\"width\":8,\"height\":2}}\n"
+        );
+        assert_eq!(
+            server.take_output(b),
+            "{\"ready\":{\"width\":8,\"height\":1}}\n"
+        );
+
+        write(
+            &mut server,
+            a,
+            b"\x1b[LChello\nworld\x1b[LG00000000000000001;",
+        );
+        write(&mut server, b, b"up\x1b[LG00000000000000002;");
+
+        let mut screen = display();
+        assert!(server.render(&mut screen).unwrap());
+        let snapshot = screen.get_ref().snapshot().clone();
+        assert_eq!(snapshot.line(0), b"hello   ");
+        assert_eq!(snapshot.line(1), b"up      ");
+        assert_eq!(snapshot.glyph(0), Some([0, 0, 0, 0, 0, 0, 0, 2]));
+        assert_eq!(snapshot.cursor(), (2, 1));
+        assert!(!server.render(&mut screen).unwrap());
+
+        // the content of the full-screen client shows again
+        server.disconnect(b);
+        server.render(&mut screen).unwrap();
+        let snapshot = screen.get_ref().snapshot();
+        assert_eq!(snapshot.line(1), b"world   ");
+        assert_eq!(snapshot.cursor(), (5, 1));
+    }
+
+    #[test]
+    fn regions_out_of_the_display() {
+        let server = Server::new(8, 2);
+        let err = server.region("s", 0, u32::MAX, 8, 1).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "region \"s\" does not fit in a 8x2 display"
+        );
+
+        let server = Server::new(8, 2);
+        assert!(server.region("s", 4, 0, 5, 1).is_err());
+        let server = Server::new(8, 2);
+        assert!(server.region("s", 4, 0, 4, 2).is_ok());
+    }
+
+    #[test]
+    fn rejected_requests() {
+        let mut server = Server::new(8, 2);
+        let (a, b) = (server.connect(), server.connect());
+        assert!(!server.request(a, r#"{"write":[104]}"#));
+        assert_eq!(server.take_output(a), "{\"error\":\"no slot opened\"}\n");
+        assert!(!server.request(a, r#"{"open":{"name":"a","region":"nope"}}"#));
+        assert_eq!(
+            server.take_output(a),
+            "{\"error\":\"unknown region \\\"nope\\\"\"}\n"
+        );
+
+        assert!(server.request(a, r#"{"open":{"name":"a"}}"#));
+        assert!(!server.request(b, r#"{"open":{"name":"a"}}"#));
+        assert_eq!(server.clients(), vec!["a"]);
+    }
+
+    #[test]
+    fn unix_socket() {
+        let path = std::env::temp_dir().join(format!("charlcd-{}.sock", std::process::id()));
+        let mut daemon = UnixServer::bind(&path, Server::new(8, 2)).unwrap();
+
+        let (done, wait) = mpsc::channel::<()>();
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            let mut screen = Screen::from_daemon(&client_path, Slot::new("test")).unwrap();
+            assert_eq!(screen.width().unwrap(), 8);
+            screen.write_all(b"hi").unwrap();
+            screen.flush().unwrap();
+            wait.recv().unwrap();
+        });
+
+        let mut screen = display();
+        for _ in 0..500 {
+            daemon.poll().unwrap();
+            daemon.server_mut().render(&mut screen).unwrap();
+            if screen.get_ref().snapshot().line(0) == b"hi      " {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(screen.get_ref().snapshot().line(0), b"hi      ");
+
+        done.send(()).unwrap();
+        client.join().unwrap();
+        drop(daemon);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_keeps_regular_files() {
+        let path = std::env::temp_dir().join(format!("charlcd-{}.file", std::process::id()));
+        fs::write(&path, b"data").unwrap();
+
+        let error = UnixServer::bind(&path, Server::new(8, 2)).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::AddrInUse);
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,479 @@
+//! Modal dialogs taking over the whole display.
+//!
+//! A dialog saves what is displayed, uses the whole display until the user
+//! answers, then puts back the previous content and display flags. Saving
+//! the display requires a [`Shadow`] screen (see [`Screen::shadowed`]), whose
+//! size is also used to lay out the dialogs.
+//!
+//! Three dialogs are available:
+//!
+//! - [`MessageBox`]: a message dismissed by the user or after a timeout;
+//! - [`Confirm`]: a yes/no question;
+//! - [`ChoiceList`]: a list of options to choose from.
+//!
+//! Dialogs are driven by navigation events like the other widgets, either
+//! with [`Dialog::handle`] and [`Dialog::render`] in an application loop or
+//! with the [`run`] helper.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use charlcd::Screen;
+//! use charlcd::dialog::{self, Confirm};
+//! use charlcd::input::{InputReader, Outcome};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut screen = screen.shadowed(width, height);
+//!     let mut input = InputReader::from_dev_path(Path::new("/dev/input/event0"))?;
+//!
+//!     let mut confirm = Confirm::new("Reboot the device?");
+//!     let answer = dialog::run(&mut screen, &mut confirm, || input.read_event().map(Some))?;
+//!
+//!     if answer == Outcome::Confirmed(true) {
+//!         println!("rebooting");
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::input::{NavEvent, Outcome};
+use crate::shadow::{Shadow, Snapshot};
+use crate::Screen;
+
+/// Delay between two polls of a non-blocking event source in [`run`].
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A widget taking over the whole display until the user answers.
+pub trait Dialog {
+    /// Value given by the user.
+    type Output;
+
+    /// Update the dialog according to a navigation event, returning the
+    /// answer of the user once given.
+    fn handle(&mut self, event: NavEvent) -> Option<Outcome<Self::Output>>;
+
+    /// Draw the whole display.
+    fn render<W: Write>(&self, screen: &mut Screen<Shadow<W>>) -> Result<()>;
+
+    /// Delay after which [`run`] gives up waiting for an answer, provided
+    /// its event source does not block.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Save what is displayed and prepare the display for a dialog: cleared,
+/// switched on, without cursor.
+///
+/// The returned snapshot can be restored with [`Snapshot::restore`] once the
+/// dialog is closed.
+pub fn take_over<W: Write>(screen: &mut Screen<Shadow<W>>) -> Result<Snapshot> {
+    let saved = screen.get_ref().snapshot().clone();
+    screen.clear()?;
+    screen.display_on()?;
+    screen.cursor_off()?;
+    screen.blink_off()?;
+    Ok(saved)
+}
+
+/// Show `dialog` until the user answers or its timeout expires, then restore
+/// the previous content of the display.
+///
+/// `next_event` is called to get the navigation events, it may either block
+/// or return `None` when no event is available. An expired timeout is
+/// reported as [`Outcome::Cancelled`].
+///
+/// The timeout is only checked between two calls to `next_event`: with a
+/// blocking source, such as [`InputReader::read_event`], the dialog stays
+/// open until the next event. Dialogs with a timeout need a source returning
+/// `None` when no event is available, such as
+/// [`InputReader::try_read_event`] or
+/// [`Keypad::try_read_event`](crate::keypad::Keypad::try_read_event).
+///
+/// The dialog is drawn when opened, then again after each event only.
+///
+/// [`InputReader::read_event`]: crate::input::InputReader::read_event
+/// [`InputReader::try_read_event`]: crate::input::InputReader::try_read_event
+pub fn run<W, D, F>(
+    screen: &mut Screen<Shadow<W>>,
+    dialog: &mut D,
+    mut next_event: F,
+) -> Result<Outcome<D::Output>>
+where
+    W: Write,
+    D: Dialog,
+    F: FnMut() -> Result<Option<NavEvent>>,
+{
+    let saved = take_over(screen)?;
+    let start = Instant::now();
+    dialog.render(screen)?;
+    screen.flush()?;
+
+    let outcome = loop {
+        if dialog.timeout().is_some_and(|t| start.elapsed() >= t) {
+            break Outcome::Cancelled;
+        }
+        match next_event()? {
+            Some(event) => {
+                if let Some(outcome) = dialog.handle(event) {
+                    break outcome;
+                }
+                dialog.render(screen)?;
+                screen.flush()?;
+            }
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    };
+
+    saved.restore(screen)?;
+    screen.flush()?;
+    Ok(outcome)
+}
+
+/// A message dismissed by any of select or back, or after a timeout.
+pub struct MessageBox {
+    text: String,
+    timeout: Option<Duration>,
+}
+
+impl MessageBox {
+    /// Create a new message box showing `text`, wrapped and centered.
+    pub fn new(text: &str) -> MessageBox {
+        MessageBox {
+            text: text.to_string(),
+            timeout: None,
+        }
+    }
+
+    /// Dismiss the message automatically after `timeout`, see [`run`] for
+    /// the event sources it requires.
+    pub fn timeout(mut self, timeout: Duration) -> MessageBox {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl Dialog for MessageBox {
+    type Output = ();
+
+    fn handle(&mut self, event: NavEvent) -> Option<Outcome<()>> {
+        match event {
+            NavEvent::Select | NavEvent::Back => Some(Outcome::Confirmed(())),
+            _ => None,
+        }
+    }
+
+    fn render<W: Write>(&self, screen: &mut Screen<Shadow<W>>) -> Result<()> {
+        let (width, height) = size(screen);
+        let lines = wrap(&self.text, width);
+        let top = height.saturating_sub(lines.len()) / 2;
+
+        let rows: Vec<Vec<u8>> = (0..height)
+            .map(
+                |row| match row.checked_sub(top).and_then(|i| lines.get(i)) {
+                    Some(line) => center(line, width),
+                    None => vec![b' '; width],
+                },
+            )
+            .collect();
+        draw(screen, &rows)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
+
+/// A yes/no question.
+pub struct Confirm {
+    question: String,
+    yes: bool,
+}
+
+impl Confirm {
+    /// Create a new question, with "No" selected.
+    pub fn new(question: &str) -> Confirm {
+        Confirm {
+            question: question.to_string(),
+            yes: false,
+        }
+    }
+
+    /// Select "Yes" instead of "No" when the dialog opens.
+    pub fn default_yes(mut self) -> Confirm {
+        self.yes = true;
+        self
+    }
+}
+
+impl Dialog for Confirm {
+    type Output = bool;
+
+    /// Any move switches between "Yes" and "No", select confirms the
+    /// selected answer and back cancels the question.
+    fn handle(&mut self, event: NavEvent) -> Option<Outcome<bool>> {
+        match event {
+            NavEvent::Select => return Some(Outcome::Confirmed(self.yes)),
+            NavEvent::Back => return Some(Outcome::Cancelled),
+            NavEvent::Rotate(steps) if steps % 2 == 0 => {}
+            _ => self.yes = !self.yes,
+        }
+        None
+    }
+
+    /// The question takes all the lines but the last one, that shows the
+    /// answers. On a single line display, the answers follow the question,
+    /// cut to fit.
+    fn render<W: Write>(&self, screen: &mut Screen<Shadow<W>>) -> Result<()> {
+        let (width, height) = size(screen);
+        let buttons: &[u8] = if self.yes {
+            b"[Yes]  No "
+        } else {
+            b" Yes  [No]"
+        };
+
+        if height == 1 {
+            let room = width.saturating_sub(buttons.len() + 1);
+            let mut row: Vec<u8> = self.question.bytes().take(room).collect();
+            row.resize(width.saturating_sub(buttons.len()), b' ');
+            row.extend_from_slice(buttons);
+            return draw(screen, &[row]);
+        }
+
+        let lines = wrap(&self.question, width);
+        let mut rows: Vec<Vec<u8>> = (0..height - 1)
+            .map(|row| match lines.get(row) {
+                Some(line) => center(line, width),
+                None => vec![b' '; width],
+            })
+            .collect();
+        rows.push(center(buttons, width));
+        draw(screen, &rows)
+    }
+}
+
+/// A list of options to choose from.
+pub struct ChoiceList {
+    title: String,
+    options: Vec<String>,
+    selected: usize,
+}
+
+impl ChoiceList {
+    /// Create a new list of `options` under `title`, with the first option
+    /// selected.
+    pub fn new<S: AsRef<str>>(title: &str, options: &[S]) -> ChoiceList {
+        ChoiceList {
+            title: title.to_string(),
+            options: options.iter().map(|o| o.as_ref().to_string()).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Select the option at `index` when the dialog opens.
+    pub fn selected(mut self, index: usize) -> ChoiceList {
+        self.selected = index.min(self.options.len().saturating_sub(1));
+        self
+    }
+}
+
+impl Dialog for ChoiceList {
+    /// Index of the chosen option.
+    type Output = usize;
+
+    /// Up and down (or a rotation) move the selection, select confirms the
+    /// selected option and back cancels the choice.
+    fn handle(&mut self, event: NavEvent) -> Option<Outcome<usize>> {
+        let steps = match event {
+            NavEvent::Up | NavEvent::Left => -1,
+            NavEvent::Down | NavEvent::Right => 1,
+            NavEvent::Rotate(steps) => steps as i64,
+            NavEvent::Select if !self.options.is_empty() => {
+                return Some(Outcome::Confirmed(self.selected))
+            }
+            NavEvent::Select => return None,
+            NavEvent::Back => return Some(Outcome::Cancelled),
+        };
+        let last = self.options.len().saturating_sub(1) as i64;
+        self.selected = (self.selected as i64 + steps).clamp(0, last) as usize;
+        None
+    }
+
+    /// The title takes the first line if the display has more than one line,
+    /// the options scroll on the other lines.
+    fn render<W: Write>(&self, screen: &mut Screen<Shadow<W>>) -> Result<()> {
+        let (width, height) = size(screen);
+        let mut rows = Vec::with_capacity(height);
+        if height > 1 {
+            rows.push(center(self.title.as_bytes(), width));
+        }
+
+        let visible = height - rows.len();
+        let scroll = (self.selected + 1).saturating_sub(visible);
+        for index in scroll..scroll + visible {
+            let mut row = Vec::with_capacity(width);
+            if let Some(option) = self.options.get(index) {
+                row.push(if index == self.selected { b'>' } else { b' ' });
+                row.extend(option.bytes());
+            }
+            row.resize(width, b' ');
+            rows.push(row);
+        }
+        draw(screen, &rows)
+    }
+}
+
+/// Size of the display tracked by the shadow of `screen`.
+fn size<W: Write>(screen: &Screen<Shadow<W>>) -> (usize, usize) {
+    let snapshot = screen.get_ref().snapshot();
+    (snapshot.width() as usize, snapshot.height().max(1) as usize)
+}
+
+/// Draw the `rows`, each of them being a whole line of the display.
+fn draw<W: Write>(screen: &mut Screen<Shadow<W>>, rows: &[Vec<u8>]) -> Result<()> {
+    let width = screen.get_ref().snapshot().width() as usize;
+    for (y, row) in rows.iter().enumerate() {
+        screen.gotoxy(0, y as u32)?;
+        screen.write_all(&row[..row.len().min(width)])?;
+    }
+    Ok(())
+}
+
+/// Pad `line` with spaces on both sides to center it in `width` characters.
+fn center(line: &[u8], width: usize) -> Vec<u8> {
+    let line = &line[..line.len().min(width)];
+    let mut row = vec![b' '; (width - line.len()) / 2];
+    row.extend_from_slice(line);
+    row.resize(width, b' ');
+    row
+}
+
+/// Split `text` into lines of at most `width` characters, breaking between
+/// words when possible.
+fn wrap(text: &str, width: usize) -> Vec<Vec<u8>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line: Vec<u8> = Vec::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.as_bytes();
+            if !line.is_empty() && line.len() + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(b' ');
+            }
+            // split the words longer than a line
+            while line.len() + word.len() > width {
+                let (head, tail) = word.split_at(width - line.len());
+                line.extend_from_slice(head);
+                lines.push(std::mem::take(&mut line));
+                word = tail;
+            }
+            line.extend_from_slice(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Screen<Shadow<Vec<u8>>> {
+        Screen::new(Vec::new()).shadowed(12, 2)
+    }
+
+    fn lines(screen: &Screen<Shadow<Vec<u8>>>) -> Vec<&[u8]> {
+        let snapshot = screen.get_ref().snapshot();
+        (0..snapshot.height()).map(|y| snapshot.line(y)).collect()
+    }
+
+    #[test]
+    fn wrap_words() {
+        assert_eq!(
+            wrap("Reboot the device now?", 10),
+            vec![b"Reboot the".to_vec(), b"device".to_vec(), b"now?".to_vec()]
+        );
+        assert_eq!(
+            wrap("abcdefghij\nxy", 4),
+            vec![
+                b"abcd".to_vec(),
+                b"efgh".to_vec(),
+                b"ij".to_vec(),
+                b"xy".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn confirm_and_restore() {
+        let mut screen = screen();
+        screen.write_all(b"Temp 21.5").unwrap();
+        screen.cursor_off().unwrap();
+        let before = screen.get_ref().snapshot().clone();
+
+        let mut events = vec![NavEvent::Select, NavEvent::Rotate(3)].into_iter();
+        let mut confirm = Confirm::new("Reboot?");
+        let answer = run(&mut screen, &mut confirm, || Ok(events.next_back())).unwrap();
+
+        assert_eq!(answer, Outcome::Confirmed(true));
+        assert_eq!(screen.get_ref().snapshot(), &before);
+    }
+
+    #[test]
+    fn render_confirm() {
+        let mut screen = screen();
+        Confirm::new("Reboot?").render(&mut screen).unwrap();
+
+        assert_eq!(lines(&screen), vec![&b"  Reboot?   "[..], b"  Yes  [No] "]);
+
+        let mut screen = Screen::new(Vec::new()).shadowed(16, 1);
+        Confirm::new("Reboot now?").render(&mut screen).unwrap();
+        assert_eq!(lines(&screen), vec![&b"Reboo  Yes  [No]"[..]]);
+    }
+
+    #[test]
+    fn choice_list() {
+        let mut screen = screen();
+        let mut choice = ChoiceList::new("Mode", &["Auto", "Manual", "Off"]);
+
+        assert_eq!(choice.handle(NavEvent::Rotate(5)), None);
+        choice.render(&mut screen).unwrap();
+        assert_eq!(lines(&screen), vec![&b"    Mode    "[..], b">Off        "]);
+        assert_eq!(choice.handle(NavEvent::Select), Some(Outcome::Confirmed(2)));
+    }
+
+    #[test]
+    fn message_timeout() {
+        let mut screen = screen();
+        let mut message = MessageBox::new("Saved").timeout(Duration::ZERO);
+
+        let outcome = run(&mut screen, &mut message, || Ok(None)).unwrap();
+        assert_eq!(outcome, Outcome::Cancelled);
+    }
+
+    #[test]
+    fn render_on_events_only() {
+        let mut screen = screen();
+        let mut message = MessageBox::new("Saved").timeout(Duration::from_millis(50));
+
+        let mut events = vec![None, Some(NavEvent::Up), None].into_iter();
+        run(&mut screen, &mut message, || Ok(events.next().flatten())).unwrap();
+
+        // drawn when opened and after the event, not on every poll
+        let output = screen.get_ref().get_ref();
+        assert_eq!(output.windows(5).filter(|w| w == b"Saved").count(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+//! Off-screen grid of characters.
+//!
+//! A [`Frame`] is drawn in memory, then rendered to a screen area in one go.
+//! This allows components to compose a whole display (e.g. a page and an
+//! indicator on top of it) without sending intermediate states to the
+//! driver.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::frame::Frame;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut frame = Frame::new(screen.width()?, screen.height()?);
+//!
+//!     frame.print(0, 0, b"Temp");
+//!     frame.print(6, 0, b"21.5");
+//!     frame.render(&mut screen, 0, 0)?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::ops::Range;
+
+use crate::Screen;
+
+/// Number of unchanged characters under which two changed runs of a line are
+/// sent as one, as moving the cursor costs more bytes than rewriting them.
+const MERGE_GAP: usize = 6;
+
+/// A grid of characters, blank when created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    cells: Vec<u8>,
+}
+
+impl Frame {
+    /// Create a new blank frame of `width` x `height` characters.
+    pub fn new(width: u32, height: u32) -> Frame {
+        Frame {
+            width,
+            height,
+            cells: vec![b' '; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Fill the frame with spaces.
+    pub fn clear(&mut self) {
+        self.cells.fill(b' ');
+    }
+
+    /// Character at the (`x`, `y`) position, if it is in the frame.
+    pub fn get(&self, x: u32, y: u32) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.cells[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Put `c` at the (`x`, `y`) position, ignored if out of the frame.
+    pub fn set(&mut self, x: u32, y: u32, c: u8) {
+        if x < self.width && y < self.height {
+            self.cells[(y * self.width + x) as usize] = c;
+        }
+    }
+
+    /// Write `text` from the (`x`, `y`) position, clipped to the end of the
+    /// line. Returns the number of characters that fit.
+    pub fn print<S: AsRef<[u8]>>(&mut self, x: u32, y: u32, text: S) -> usize {
+        let text = text.as_ref();
+        if y >= self.height || x >= self.width {
+            return 0;
+        }
+        let count = text.len().min((self.width - x) as usize);
+        let start = (y * self.width + x) as usize;
+        self.cells[start..start + count].copy_from_slice(&text[..count]);
+        count
+    }
+
+    /// Characters of line `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not a line of the frame.
+    pub fn line(&self, y: u32) -> &[u8] {
+        assert!(y < self.height, "line out of the frame");
+        let start = (y * self.width) as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    /// Draw the whole frame with its top-left corner at the (`x`, `y`)
+    /// position.
+    pub fn render<T: Write>(&self, screen: &mut Screen<T>, x: u32, y: u32) -> Result<()> {
+        for row in 0..self.height {
+            screen.gotoxy(x, y + row)?;
+            screen.write_all(self.line(row))?;
+        }
+        Ok(())
+    }
+
+    /// Draw only the characters that differ from `previous`, with the
+    /// top-left corner of the frame at the (`x`, `y`) position.
+    ///
+    /// Returns the number of characters written, `0` if the frames are
+    /// identical.
+    ///
+    /// # Panics
+    ///
+    /// Panics if both frames do not have the same size.
+    pub fn render_diff<T: Write>(
+        &self,
+        previous: &Frame,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+    ) -> Result<usize> {
+        assert!(
+            self.width == previous.width && self.height == previous.height,
+            "frames must have the same size"
+        );
+
+        let mut written = 0;
+        for row in 0..self.height {
+            let (line, old) = (self.line(row), previous.line(row));
+            let mut runs: Vec<Range<usize>> = Vec::new();
+            for i in (0..line.len()).filter(|&i| line[i] != old[i]) {
+                match runs.last_mut() {
+                    Some(run) if i - run.end <= MERGE_GAP => run.end = i + 1,
+                    _ => runs.push(i..i + 1),
+                }
+            }
+
+            for run in runs {
+                screen.gotoxy(x + run.start as u32, y + row)?;
+                screen.write_all(&line[run.clone()])?;
+                written += run.len();
+            }
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_clipped() {
+        let mut frame = Frame::new(6, 2);
+        assert_eq!(frame.print(2, 1, b"hello"), 4);
+        assert_eq!(frame.print(6, 0, b"out"), 0);
+        frame.set(0, 0, b'>');
+        frame.set(9, 9, b'!');
+
+        assert_eq!(frame.line(0), b">     ");
+        assert_eq!(frame.line(1), b"  hell");
+        assert_eq!(frame.get(2, 1), Some(b'h'));
+        assert_eq!(frame.get(6, 1), None);
+    }
+
+    #[test]
+    fn render() {
+        let mut frame = Frame::new(3, 2);
+        frame.print(0, 1, b"ab");
+
+        let mut buf = Vec::new();
+        frame.render(&mut Screen::new(&mut buf), 1, 2).unwrap();
+        assert_eq!(buf, b"\x1b[Lx1y2;   \x1b[Lx1y3;ab ".to_vec());
+    }
+
+    #[test]
+    fn render_diff() {
+        let previous = Frame::new(20, 2);
+        let mut frame = previous.clone();
+        frame.print(1, 0, b"a");
+        frame.print(5, 0, b"b");
+        frame.print(19, 0, b"c");
+
+        let mut buf = Vec::new();
+        let written = frame
+            .render_diff(&previous, &mut Screen::new(&mut buf), 0, 0)
+            .unwrap();
+        assert_eq!(written, 6);
+        assert_eq!(buf, b"\x1b[Lx1y0;a   b\x1b[Lx19y0;c".to_vec());
+
+        buf.clear();
+        let written = frame
+            .render_diff(&frame, &mut Screen::new(&mut buf), 0, 0)
+            .unwrap();
+        assert_eq!((written, buf.len()), (0, 0));
+    }
+}
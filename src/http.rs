@@ -0,0 +1,686 @@
+//! Inspect and drive a display over HTTP, for debugging.
+//!
+//! A [`Preview`] serves the [`Snapshot`] of a shadowed screen (see
+//! [`shadow`][crate::shadow]) on a local TCP port:
+//!
+//! * `GET /` shows an HTML page rendering the display as an HD44780 would,
+//!   custom characters included, refreshed every second;
+//! * `GET /screen` returns the snapshot as a JSON object with the `width`,
+//!   `height`, `lines`, `cursor`, `flags` and `glyphs` of the display;
+//! * `POST /text` writes the body of the request at the cursor position;
+//! * `POST /command` runs the commands of the body, one per line: `clear`,
+//!   `home`, `reinit`, `goto X Y`, `flash_backlight`, and `display`,
+//!   `cursor`, `blink` or `backlight` followed by `on` or `off`.
+//!
+//! Both `POST` endpoints reply with the JSON snapshot of the display once
+//! updated, and reject a request with a `400 Bad Request` before writing
+//! anything if its body is invalid.
+//!
+//! There is no authentication: the preview only listens on a loopback
+//! address and only accepts local clients. Use an SSH tunnel to reach it
+//! from another host. So that web pages open in a local browser cannot use
+//! it either, requests must name a loopback host in their `Host` header
+//! (against DNS rebinding), and requests sent by a page from another origin
+//! than the preview itself, including pages served by other local ports, are
+//! rejected with a `403 Forbidden`.
+//!
+//! This module requires the `http` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::thread;
+//! use std::time::Duration;
+//! use charlcd::Screen;
+//! use charlcd::http::Preview;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut screen = screen.shadowed(width, height);
+//!     screen.reinit()?;
+//!
+//!     // browse http://localhost:8044/ to see the display
+//!     let mut preview = Preview::bind("127.0.0.1:8044", screen)?;
+//!     loop {
+//!         preview.screen_mut().write(b".")?;
+//!         preview.screen_mut().flush()?;
+//!         preview.poll()?;
+//!         thread::sleep(Duration::from_millis(100));
+//!     }
+//! }
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use crate::shadow::{Shadow, Snapshot};
+use crate::Screen;
+
+/// Time given to a client to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Largest request body accepted.
+const MAX_BODY_LEN: usize = 4096;
+
+/// Largest request accepted, request line and headers included.
+const MAX_REQUEST_LEN: u64 = 2 * MAX_BODY_LEN as u64;
+
+/// An HTTP response: status line, content type and body.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: String) -> Response {
+        Response {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    fn text(status: &'static str, body: &str) -> Response {
+        Response::new(status, "text/plain; charset=utf-8", format!("{}\n", body))
+    }
+
+    fn write_to<S: Write>(&self, stream: &mut S) -> Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        stream.flush()
+    }
+}
+
+/// A command of the `POST /command` endpoint.
+enum Action {
+    Clear,
+    Home,
+    Reinit,
+    Goto(u32, u32),
+    FlashBacklight,
+    Display(bool),
+    Cursor(bool),
+    Blink(bool),
+    Backlight(bool),
+}
+
+impl Action {
+    fn parse(line: &str) -> Option<Action> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let on = |word: &str| match word {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        };
+        Some(match words[..] {
+            ["clear"] => Action::Clear,
+            ["home"] => Action::Home,
+            ["reinit"] => Action::Reinit,
+            ["goto", x, y] => Action::Goto(x.parse().ok()?, y.parse().ok()?),
+            ["flash_backlight"] => Action::FlashBacklight,
+            ["display", state] => Action::Display(on(state)?),
+            ["cursor", state] => Action::Cursor(on(state)?),
+            ["blink", state] => Action::Blink(on(state)?),
+            ["backlight", state] => Action::Backlight(on(state)?),
+            _ => return None,
+        })
+    }
+
+    fn run<T: Write>(&self, screen: &mut Screen<T>) -> Result<()> {
+        match *self {
+            Action::Clear => screen.clear(),
+            Action::Home => screen.write_all(b"\x1b[H"),
+            Action::Reinit => screen.reinit(),
+            Action::Goto(x, y) => screen.gotoxy(x, y),
+            Action::FlashBacklight => screen.flash_backlight(),
+            Action::Display(true) => screen.display_on(),
+            Action::Display(false) => screen.display_off(),
+            Action::Cursor(true) => screen.cursor_on(),
+            Action::Cursor(false) => screen.cursor_off(),
+            Action::Blink(true) => screen.blink_on(),
+            Action::Blink(false) => screen.blink_off(),
+            Action::Backlight(true) => screen.backlight_on(),
+            Action::Backlight(false) => screen.backlight_off(),
+        }
+    }
+}
+
+/// The JSON representation of `snapshot`.
+///
+/// Lines are decoded as Latin-1, so that each character of a line is a cell
+/// of the display, custom characters being `\u0000` to `\u0007`.
+fn to_json(snapshot: &Snapshot) -> String {
+    let lines: Vec<String> = (0..snapshot.height())
+        .map(|y| snapshot.line(y).iter().map(|&c| char::from(c)).collect())
+        .collect();
+    let glyphs: Vec<Option<[u8; 8]>> = (0..8).map(|slot| snapshot.glyph(slot)).collect();
+    let (x, y) = snapshot.cursor();
+    let flags = snapshot.flags();
+    json!({
+        "width": snapshot.width(),
+        "height": snapshot.height(),
+        "lines": lines,
+        "cursor": { "x": x, "y": y },
+        "flags": {
+            "display": flags.display,
+            "cursor": flags.cursor,
+            "blink": flags.blink,
+            "backlight": flags.backlight,
+        },
+        "glyphs": glyphs,
+    })
+    .to_string()
+}
+
+/// An HTML page rendering `snapshot` as an HD44780 display.
+fn to_html(snapshot: &Snapshot) -> String {
+    let flags = snapshot.flags();
+    let mut html = String::from(concat!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">",
+        "<meta http-equiv=\"refresh\" content=\"1\"><title>charlcd</title><style>",
+        "body{background:#222;display:flex;justify-content:center;padding:2em}",
+        ".lcd{background:#7c3;padding:12px;border:10px solid #111;border-radius:6px}",
+        ".lcd.dark{background:#354}.lcd.off .cell{visibility:hidden}",
+        ".row{display:flex}.cell{display:grid;grid-template-columns:repeat(5,4px);",
+        "grid-gap:1px;margin:2px;padding:1px}",
+        ".cell i{width:4px;height:4px;background:rgba(0,0,0,.06)}",
+        ".cell i.on{background:#123}.cell.cursor{outline:2px solid #123}",
+        ".cell.text{display:block;width:24px;height:40px;font:32px/40px monospace;",
+        "color:#123;text-align:center;padding:0}",
+        "</style></head><body>\n",
+    ));
+    let _ = writeln!(
+        html,
+        "<div class=\"lcd{}{}\">",
+        if flags.backlight { "" } else { " dark" },
+        if flags.display { "" } else { " off" }
+    );
+    let cursor = snapshot.cursor();
+    for y in 0..snapshot.height() {
+        html.push_str("<div class=\"row\">");
+        for (x, &c) in snapshot.line(y).iter().enumerate() {
+            let mark = if flags.cursor && cursor == (x as u32, y) {
+                " cursor"
+            } else {
+                ""
+            };
+            match snapshot.glyph(c).filter(|_| c < 8) {
+                Some(glyph) => {
+                    let _ = write!(html, "<span class=\"cell{}\">", mark);
+                    for row in &glyph {
+                        for bit in (0..5).rev() {
+                            let on = if row >> bit & 1 == 1 {
+                                " class=\"on\""
+                            } else {
+                                ""
+                            };
+                            let _ = write!(html, "<i{}></i>", on);
+                        }
+                    }
+                    html.push_str("</span>");
+                }
+                None => {
+                    let _ = write!(html, "<span class=\"cell text{}\">", mark);
+                    match c {
+                        b'&' => html.push_str("&amp;"),
+                        b'<' => html.push_str("&lt;"),
+                        b'>' => html.push_str("&gt;"),
+                        b' ' => html.push_str("&nbsp;"),
+                        0x21..=0x7e => html.push(char::from(c)),
+                        _ => html.push('\u{fffd}'),
+                    }
+                    html.push_str("</span>");
+                }
+            }
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</div>\n</body></html>\n");
+    html
+}
+
+/// A local HTTP server previewing and driving a shadowed screen.
+///
+/// Nothing blocks but the handling of a request: [`poll`](Preview::poll)
+/// serves the pending requests, and has to be called regularly.
+pub struct Preview<T>
+where
+    T: Write,
+{
+    listener: TcpListener,
+    screen: Screen<Shadow<T>>,
+}
+
+impl<T> Preview<T>
+where
+    T: Write,
+{
+    /// Listen on `addr` for requests about `screen`.
+    ///
+    /// Fails with [`ErrorKind::AddrNotAvailable`] if `addr` is not a
+    /// loopback address.
+    pub fn bind<A: ToSocketAddrs>(addr: A, screen: Screen<Shadow<T>>) -> Result<Preview<T>> {
+        let listener = TcpListener::bind(addr)?;
+        if !listener.local_addr()?.ip().is_loopback() {
+            return Err(Error::new(
+                ErrorKind::AddrNotAvailable,
+                "the preview only listens on loopback addresses",
+            ));
+        }
+        listener.set_nonblocking(true)?;
+        Ok(Preview { listener, screen })
+    }
+
+    /// Address the preview listens on, e.g. to get the port picked by the
+    /// system when binding port `0`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn screen(&self) -> &Screen<Shadow<T>> {
+        &self.screen
+    }
+
+    /// Get a mutable reference to the screen, to keep drawing on it.
+    pub fn screen_mut(&mut self) -> &mut Screen<Shadow<T>> {
+        &mut self.screen
+    }
+
+    /// Unwrap this preview, returning the screen.
+    pub fn into_inner(self) -> Screen<Shadow<T>> {
+        self.screen
+    }
+
+    /// Serve the pending requests, returning how many were served.
+    ///
+    /// Each request is read and answered before returning: a client slow to
+    /// send its request blocks the caller for up to a second, after which
+    /// its connection is closed.
+    ///
+    /// Errors of a client only close its connection, only errors of the
+    /// listening socket and of the screen are returned.
+    pub fn poll(&mut self) -> Result<usize> {
+        let mut served = 0;
+        loop {
+            let (stream, peer) = match self.listener.accept() {
+                Ok(client) => client,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(served),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if !peer.ip().is_loopback() {
+                continue;
+            }
+            if self.serve(stream)? {
+                served += 1;
+            }
+        }
+    }
+
+    /// Read a request from `stream` and answer it, returning whether it was
+    /// served.
+    fn serve(&mut self, mut stream: TcpStream) -> Result<bool> {
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        let request = stream.set_nonblocking(false).and_then(|_| {
+            let reader = Deadline {
+                stream: &stream,
+                deadline,
+            };
+            read_request(reader.take(MAX_REQUEST_LEN))
+        });
+        let response = match request {
+            Ok(Some(request)) if !request.is_local() => {
+                Response::text("403 Forbidden", "only local clients are allowed")
+            }
+            Ok(Some(request)) => self.respond(&request.method, &request.path, &request.body)?,
+            Ok(None) => Response::text("400 Bad Request", "malformed request"),
+            Err(_) => return Ok(false),
+        };
+        Ok(response.write_to(&mut stream).is_ok())
+    }
+
+    /// Answer the request for `path` with `method`.
+    fn respond(&mut self, method: &str, path: &str, body: &[u8]) -> Result<Response> {
+        let path = path.split('?').next().unwrap_or(path);
+        let response = match (method, path) {
+            ("GET", "/") => Response::new(
+                "200 OK",
+                "text/html; charset=utf-8",
+                to_html(self.screen.get_ref().snapshot()),
+            ),
+            ("GET", "/screen") => self.snapshot(),
+            ("POST", "/text") => {
+                if body.contains(&0x1b) {
+                    return Ok(Response::text(
+                        "400 Bad Request",
+                        "escape sequences are not text, use /command",
+                    ));
+                }
+                self.screen.write_all(body)?;
+                self.screen.flush()?;
+                self.snapshot()
+            }
+            ("POST", "/command") => {
+                let actions = std::str::from_utf8(body).ok().and_then(|body| {
+                    body.lines()
+                        .filter(|line| !line.trim().is_empty())
+                        .map(Action::parse)
+                        .collect::<Option<Vec<_>>>()
+                });
+                let actions = match actions {
+                    Some(actions) => actions,
+                    None => return Ok(Response::text("400 Bad Request", "unknown command")),
+                };
+                for action in &actions {
+                    action.run(&mut self.screen)?;
+                }
+                self.screen.flush()?;
+                self.snapshot()
+            }
+            (_, "/") | (_, "/screen") | (_, "/text") | (_, "/command") => {
+                Response::text("405 Method Not Allowed", "method not allowed")
+            }
+            _ => Response::text("404 Not Found", "not found"),
+        };
+        Ok(response)
+    }
+
+    fn snapshot(&self) -> Response {
+        Response::new(
+            "200 OK",
+            "application/json",
+            to_json(self.screen.get_ref().snapshot()),
+        )
+    }
+}
+
+/// A reader of a stream failing with [`ErrorKind::TimedOut`] once
+/// `deadline` is passed, however slowly the bytes come.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> Read for Deadline<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::from(ErrorKind::TimedOut));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read a line ending with a line feed into `line`, returning whether one
+/// was read.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<bool> {
+    line.clear();
+    reader.read_line(line)?;
+    Ok(line.ends_with('\n'))
+}
+
+/// Whether `host`, a host name with an optional port, is a loopback host.
+fn is_loopback(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.split(':').next().unwrap_or(host),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// The parts of an HTTP request used by the preview.
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Whether the request is for a loopback host, and does not come from a
+    /// web page of another origin than the preview.
+    fn is_local(&self) -> bool {
+        let host = match self.host.as_deref() {
+            Some(host) if is_loopback(host) => host,
+            _ => return false,
+        };
+        // pages of the preview were loaded from the host of the request
+        match self.origin.as_deref() {
+            Some(origin) => origin
+                .strip_prefix("http://")
+                .is_some_and(|origin| origin.eq_ignore_ascii_case(host)),
+            None => true,
+        }
+    }
+}
+
+/// Read an HTTP request, `None` if it is malformed or too long.
+fn read_request<S: Read>(stream: S) -> Result<Option<Request>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if !read_line(&mut reader, &mut line)? {
+        return Ok(None);
+    }
+    let mut words = line.split_whitespace();
+    let (method, path) = match (words.next(), words.next(), words.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/") => {
+            (method.to_string(), path.to_string())
+        }
+        _ => return Ok(None),
+    };
+
+    let (mut length, mut host, mut origin) = (0, None, None);
+    loop {
+        if !read_line(&mut reader, &mut line)? {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = match value.parse() {
+                    Ok(length) if length <= MAX_BODY_LEN => length,
+                    _ => return Ok(None),
+                };
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            }
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request {
+        method,
+        path,
+        host,
+        origin,
+        body,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_char;
+    use std::thread;
+
+    fn preview() -> Preview<Vec<u8>> {
+        let screen = Screen::new(Vec::new()).shadowed(8, 2);
+        Preview::bind("127.0.0.1:0", screen).unwrap()
+    }
+
+    /// Send a request to `preview` from another thread, returning the status
+    /// line and body of the response.
+    fn request(
+        preview: &mut Preview<Vec<u8>>,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (String, String) {
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        send(preview, request.into_bytes())
+    }
+
+    /// Send the raw `request` to `preview` from another thread, returning the
+    /// status line and body of the response.
+    fn send(preview: &mut Preview<Vec<u8>>, request: Vec<u8>) -> (String, String) {
+        let addr = preview.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&request).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        while !client.is_finished() {
+            preview.poll().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let response = client.join().unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.lines().next().unwrap();
+        (status.to_string(), body.to_string())
+    }
+
+    #[test]
+    fn json_snapshot() {
+        let mut preview = preview();
+        preview.screen_mut().write_all(b"ab\x00").unwrap();
+        preview
+            .screen_mut()
+            .custom_char(0, custom_char::UP_TRIANGLE)
+            .unwrap();
+
+        let (status, body) = request(&mut preview, "GET", "/screen", "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["lines"], json!(["ab\u{0}     ", "        "]));
+        assert_eq!(json["cursor"], json!({"x": 3, "y": 0}));
+        assert_eq!(json["flags"]["backlight"], json!(true));
+        assert_eq!(json["glyphs"][0], json!(custom_char::UP_TRIANGLE));
+        assert_eq!(json["glyphs"][1], json!(null));
+
+        let (status, body) = request(&mut preview, "GET", "/", "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("<span class=\"cell text\">a</span>"));
+        assert_eq!(body.matches("<i class=\"on\">").count(), 9);
+    }
+
+    #[test]
+    fn post_text_and_commands() {
+        let mut preview = preview();
+
+        let (status, _) = request(&mut preview, "POST", "/text", "hi\nyou");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let (status, body) = request(
+            &mut preview,
+            "POST",
+            "/command",
+            "goto 1 0\nbacklight off\n",
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["lines"], json!(["hi      ", "you     "]));
+        assert_eq!(json["cursor"], json!({"x": 1, "y": 0}));
+        assert_eq!(json["flags"]["backlight"], json!(false));
+
+        // nothing is written for invalid requests
+        let written = preview.screen().get_ref().get_ref().len();
+        let (status, _) = request(&mut preview, "POST", "/command", "clear\nexplode");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        let (status, _) = request(&mut preview, "POST", "/text", "\x1b[Lb");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(preview.screen().get_ref().get_ref().len(), written);
+
+        assert_eq!(
+            request(&mut preview, "GET", "/text", "").0,
+            "HTTP/1.1 405 Method Not Allowed"
+        );
+        assert_eq!(
+            request(&mut preview, "GET", "/nope", "").0,
+            "HTTP/1.1 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn foreign_requests() {
+        let mut preview = preview();
+        let forbidden = [
+            "POST /text HTTP/1.1\r\nHost: evil.example:8044\r\nContent-Length: 1\r\n\r\nx",
+            "POST /text HTTP/1.1\r\nHost: localhost\r\nOrigin: http://evil.example\r\n\
+             Content-Length: 1\r\n\r\nx",
+            "POST /text HTTP/1.1\r\nContent-Length: 1\r\n\r\nx",
+            "POST /text HTTP/1.1\r\nHost: localhost:8044\r\nOrigin: http://localhost:3000\r\n\
+             Content-Length: 1\r\n\r\nx",
+        ];
+        for request in &forbidden {
+            let (status, _) = send(&mut preview, request.as_bytes().to_vec());
+            assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        }
+        assert!(preview.screen().get_ref().get_ref().is_empty());
+
+        let request = "GET /screen HTTP/1.1\r\nHost: [::1]:8044\r\n\
+                       Origin: http://[::1]:8044\r\n\r\n";
+        let (status, _) = send(&mut preview, request.as_bytes().to_vec());
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn bounded_requests() {
+        let mut preview = preview();
+
+        let mut request = b"GET /".to_vec();
+        request.resize(MAX_REQUEST_LEN as usize, b'a');
+        assert_eq!(send(&mut preview, request).0, "HTTP/1.1 400 Bad Request");
+
+        // a client sending its request slowly is dropped after the timeout
+        let mut stream = TcpStream::connect(preview.local_addr().unwrap()).unwrap();
+        let client = thread::spawn(move || {
+            for &b in b"GET / HTTP/1.1\r\nHost: localhost\r\n" {
+                if stream.write_all(&[b]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        let start = Instant::now();
+        assert_eq!(preview.poll().unwrap(), 0);
+        assert!(start.elapsed() < REQUEST_TIMEOUT * 2);
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn loopback_only() {
+        let screen = Screen::new(Vec::new()).shadowed(8, 2);
+        let error = Preview::bind("0.0.0.0:0", screen).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::AddrNotAvailable);
+    }
+}
@@ -0,0 +1,559 @@
+//! Navigate with buttons and rotary encoders of the evdev input subsystem.
+//!
+//! Front panel buttons wired through the `gpio-keys` driver and rotary
+//! encoders handled by the `rotary-encoder` driver show up as
+//! `/dev/input/event*` devices. An [`InputReader`] parses the
+//! `struct input_event` records of such a device (or of whatever that
+//! implements the [`Read`] trait) and turns them into [`NavEvent`]s, that are
+//! abstract enough to drive menus and other widgets from any input source.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use charlcd::input::{InputReader, NavEvent};
+//! use std::path::Path;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut input = InputReader::from_dev_path(Path::new("/dev/input/event0"))?;
+//!
+//!     loop {
+//!         match input.read_event()? {
+//!             NavEvent::Back => break,
+//!             event => println!("{:?}", event),
+//!         }
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Result};
+use std::mem::size_of;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+use crate::keypad::{Key, KeyAction, KeyEvent};
+
+/// Synchronization event type.
+pub const EV_SYN: u16 = 0x00;
+/// Key or button event type.
+pub const EV_KEY: u16 = 0x01;
+/// Relative axis event type.
+pub const EV_REL: u16 = 0x02;
+/// Absolute axis event type.
+pub const EV_ABS: u16 = 0x03;
+
+pub const KEY_ESC: u16 = 1;
+pub const KEY_ENTER: u16 = 28;
+pub const KEY_KPENTER: u16 = 96;
+pub const KEY_UP: u16 = 103;
+pub const KEY_LEFT: u16 = 105;
+pub const KEY_RIGHT: u16 = 106;
+pub const KEY_DOWN: u16 = 108;
+pub const KEY_BACK: u16 = 158;
+pub const KEY_OK: u16 = 0x160;
+pub const KEY_SELECT: u16 = 0x161;
+
+/// Default relative axis of the `rotary-encoder` driver.
+pub const REL_X: u16 = 0x00;
+pub const REL_DIAL: u16 = 0x07;
+pub const REL_WHEEL: u16 = 0x08;
+/// Default absolute axis of the `rotary-encoder` driver.
+pub const ABS_X: u16 = 0x00;
+
+/// Size of a `struct input_event` record on this platform.
+pub const INPUT_EVENT_SIZE: usize = 2 * size_of::<libc::c_long>() + 8;
+
+/// Delay between two reads of a non-blocking device waiting for an event.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An abstract navigation event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavEvent {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+    /// Rotation of an encoder by a number of steps, positive clockwise.
+    Rotate(i32),
+}
+
+/// How an input widget was closed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// The user confirmed the given value.
+    Confirmed(T),
+    /// The user gave up, the value should be left unchanged.
+    Cancelled,
+}
+
+impl KeyEvent {
+    /// Navigation event matching a keypad key event, if any.
+    ///
+    /// Key presses and repeats are mapped, releases are not.
+    pub fn nav_event(&self) -> Option<NavEvent> {
+        if self.action == KeyAction::Release {
+            return None;
+        }
+        match self.key {
+            Key::Up => Some(NavEvent::Up),
+            Key::Down => Some(NavEvent::Down),
+            Key::Left => Some(NavEvent::Left),
+            Key::Right => Some(NavEvent::Right),
+            Key::Enter => Some(NavEvent::Select),
+            Key::Escape => Some(NavEvent::Back),
+            _ => None,
+        }
+    }
+}
+
+/// A raw `struct input_event` record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub sec: i64,
+    pub usec: i64,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    /// Read a single record from `reader`.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<InputEvent> {
+        let mut buf = [0u8; INPUT_EVENT_SIZE];
+        reader.read_exact(&mut buf)?;
+
+        let mut buf = &buf[..];
+        let (sec, usec) = if size_of::<libc::c_long>() == 8 {
+            (
+                buf.read_i64::<NativeEndian>()?,
+                buf.read_i64::<NativeEndian>()?,
+            )
+        } else {
+            (
+                buf.read_i32::<NativeEndian>()? as i64,
+                buf.read_i32::<NativeEndian>()? as i64,
+            )
+        };
+
+        Ok(InputEvent {
+            sec,
+            usec,
+            kind: buf.read_u16::<NativeEndian>()?,
+            code: buf.read_u16::<NativeEndian>()?,
+            value: buf.read_i32::<NativeEndian>()?,
+        })
+    }
+}
+
+/// Mapping between input events and navigation events.
+#[derive(Clone, Debug)]
+pub struct InputMap {
+    keys: HashMap<u16, NavEvent>,
+    rel_axes: Vec<u16>,
+    abs_axes: Vec<u16>,
+    // number of positions of the absolute axes wrapping around
+    rollovers: HashMap<u16, i32>,
+}
+
+impl Default for InputMap {
+    /// Map the arrow keys, the usual validation and cancellation keys, and
+    /// the default axes of the `rotary-encoder` driver.
+    fn default() -> Self {
+        InputMap::empty()
+            .key(KEY_UP, NavEvent::Up)
+            .key(KEY_DOWN, NavEvent::Down)
+            .key(KEY_LEFT, NavEvent::Left)
+            .key(KEY_RIGHT, NavEvent::Right)
+            .key(KEY_ENTER, NavEvent::Select)
+            .key(KEY_KPENTER, NavEvent::Select)
+            .key(KEY_OK, NavEvent::Select)
+            .key(KEY_SELECT, NavEvent::Select)
+            .key(KEY_ESC, NavEvent::Back)
+            .key(KEY_BACK, NavEvent::Back)
+            .rel_axis(REL_X)
+            .rel_axis(REL_DIAL)
+            .rel_axis(REL_WHEEL)
+            .abs_axis(ABS_X)
+    }
+}
+
+impl InputMap {
+    /// Create a map without any binding.
+    pub fn empty() -> InputMap {
+        InputMap {
+            keys: HashMap::new(),
+            rel_axes: Vec::new(),
+            abs_axes: Vec::new(),
+            rollovers: HashMap::new(),
+        }
+    }
+
+    /// Report presses and repeats of the key `code` as `event`.
+    ///
+    /// The key codes are the `KEY_*` and `BTN_*` values of the
+    /// `linux,code` property of `gpio-keys` device-tree nodes.
+    pub fn key(mut self, code: u16, event: NavEvent) -> InputMap {
+        self.keys.insert(code, event);
+        self
+    }
+
+    /// Report the moves of the relative axis `code` as rotations.
+    pub fn rel_axis(mut self, code: u16) -> InputMap {
+        self.rel_axes.push(code);
+        self
+    }
+
+    /// Report the position changes of the absolute axis `code` as rotations.
+    /// The first position reported only sets the reference, and changes too
+    /// large for an `i32` are ignored.
+    ///
+    /// The position is not expected to wrap around, see
+    /// [`InputMap::rollover_axis`] for encoders that do.
+    pub fn abs_axis(mut self, code: u16) -> InputMap {
+        self.abs_axes.push(code);
+        self.rollovers.remove(&code);
+        self
+    }
+
+    /// Report the position changes of the absolute axis `code` as rotations,
+    /// the position wrapping around after `steps` positions, as with the
+    /// `rotary-encoder,rollover` property of the `rotary-encoder` driver.
+    ///
+    /// Each change is taken the shortest way around: going from the last
+    /// position to the first one is a single step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps` is not positive.
+    pub fn rollover_axis(mut self, code: u16, steps: i32) -> InputMap {
+        assert!(steps > 0, "an axis needs at least one position");
+        self.abs_axes.push(code);
+        self.rollovers.insert(code, steps);
+        self
+    }
+}
+
+/// A reader of navigation events from an evdev device (or whatever that
+/// implements the [`Read`] trait).
+pub struct InputReader<R> {
+    reader: R,
+    map: InputMap,
+    positions: HashMap<u16, i32>,
+    // bytes read but not parsed yet, less than a record between two calls
+    pending: Vec<u8>,
+}
+
+impl<R> InputReader<R>
+where
+    R: Read,
+{
+    /// Create a new [`InputReader`] instance that will read the input events
+    /// from the provided [`Read`], using the default map.
+    pub fn new(reader: R) -> InputReader<R> {
+        InputReader {
+            reader,
+            map: InputMap::default(),
+            positions: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Translate the input events with `map` instead of the default map.
+    pub fn map(mut self, map: InputMap) -> InputReader<R> {
+        self.map = map;
+        self
+    }
+
+    /// Wait for the next raw input event.
+    pub fn read_raw(&mut self) -> Result<InputEvent> {
+        loop {
+            if let Some(raw) = self.pop_raw() {
+                return Ok(raw);
+            }
+            match self.fill() {
+                Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "input closed")),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Wait for the next navigation event, skipping the input events that
+    /// are not mapped.
+    ///
+    /// An error of kind [`ErrorKind::UnexpectedEof`] is returned if the end of
+    /// the input is reached.
+    pub fn read_event(&mut self) -> Result<NavEvent> {
+        loop {
+            let raw = self.read_raw()?;
+            if let Some(event) = self.translate(&raw) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Get the next navigation event if one is available, `None` otherwise.
+    ///
+    /// This never blocks as long as the underlying reader is non-blocking,
+    /// which is the case of the device opened by
+    /// [`InputReader::from_dev_path`].
+    pub fn try_read_event(&mut self) -> Result<Option<NavEvent>> {
+        loop {
+            while let Some(raw) = self.pop_raw() {
+                if let Some(event) = self.translate(&raw) {
+                    return Ok(Some(event));
+                }
+            }
+            match self.fill() {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Iterate over the navigation events, until the end of the input.
+    pub fn events(&mut self) -> Events<'_, R> {
+        Events { input: self }
+    }
+
+    /// Navigation event matching a raw input event, if any.
+    pub fn translate(&mut self, raw: &InputEvent) -> Option<NavEvent> {
+        match raw.kind {
+            // value is 0 on release, 1 on press and 2 on autorepeat
+            EV_KEY if raw.value != 0 => self.map.keys.get(&raw.code).copied(),
+            EV_REL if self.map.rel_axes.contains(&raw.code) && raw.value != 0 => {
+                Some(NavEvent::Rotate(raw.value))
+            }
+            EV_ABS if self.map.abs_axes.contains(&raw.code) => {
+                // the first position of an axis only gives the reference
+                let last = self.positions.insert(raw.code, raw.value)?;
+                let delta = match self.map.rollovers.get(&raw.code) {
+                    Some(&steps) => {
+                        let delta = (raw.value as i64 - last as i64).rem_euclid(steps as i64);
+                        if delta > steps as i64 / 2 {
+                            (delta - steps as i64) as i32
+                        } else {
+                            delta as i32
+                        }
+                    }
+                    None => raw.value.checked_sub(last)?,
+                };
+                match delta {
+                    0 => None,
+                    delta => Some(NavEvent::Rotate(delta)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Read the available bytes, returning how many were read.
+    fn fill(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 4 * INPUT_EVENT_SIZE];
+        let count = self.reader.read(&mut buf)?;
+        self.pending.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+
+    /// Decode the first record of the pending bytes, if complete.
+    fn pop_raw(&mut self) -> Option<InputEvent> {
+        if self.pending.len() < INPUT_EVENT_SIZE {
+            return None;
+        }
+        let raw = InputEvent::read_from(&mut &self.pending[..INPUT_EVENT_SIZE]).ok();
+        self.pending.drain(..INPUT_EVENT_SIZE);
+        raw
+    }
+}
+
+/// Iterator over the navigation events of an [`InputReader`], see
+/// [`InputReader::events`].
+pub struct Events<'a, R> {
+    input: &'a mut InputReader<R>,
+}
+
+impl<'a, R> Iterator for Events<'a, R>
+where
+    R: Read,
+{
+    type Item = Result<NavEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.input.read_event() {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            res => Some(res),
+        }
+    }
+}
+
+impl InputReader<File> {
+    /// Create an InputReader instance based on the passed path to the
+    /// device, e.g. `/dev/input/event0`.
+    ///
+    /// The device is opened in non-blocking mode, so that
+    /// [`InputReader::try_read_event`] returns immediately.
+    pub fn from_dev_path(path: &Path) -> Result<InputReader<File>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(InputReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn record(kind: u16, code: u16, value: i32) -> Vec<u8> {
+        let long = size_of::<libc::c_long>();
+        let mut buf = vec![0u8; 2 * long]; // timestamp
+        buf[0] = 42;
+        buf.extend(&kind.to_ne_bytes());
+        buf.extend(&code.to_ne_bytes());
+        buf.extend(&value.to_ne_bytes());
+        buf
+    }
+
+    fn events(records: &[Vec<u8>]) -> Vec<NavEvent> {
+        let mut input = InputReader::new(Cursor::new(records.concat()));
+        input.events().map(|e| e.unwrap()).collect()
+    }
+
+    #[test]
+    fn raw_record() {
+        let bytes = record(EV_KEY, KEY_UP, 1);
+        assert_eq!(bytes.len(), INPUT_EVENT_SIZE);
+
+        let event = InputEvent::read_from(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(event.kind, EV_KEY);
+        assert_eq!(event.code, KEY_UP);
+        assert_eq!(event.value, 1);
+    }
+
+    #[test]
+    fn gpio_keys() {
+        let records = [
+            record(EV_KEY, KEY_DOWN, 1),
+            record(EV_SYN, 0, 0),
+            record(EV_KEY, KEY_DOWN, 2),
+            record(EV_SYN, 0, 0),
+            record(EV_KEY, KEY_DOWN, 0),
+            record(EV_SYN, 0, 0),
+            record(EV_KEY, KEY_ENTER, 1),
+            record(EV_KEY, 0x100, 1), // BTN_0, not mapped
+        ];
+        assert_eq!(
+            events(&records),
+            vec![NavEvent::Down, NavEvent::Down, NavEvent::Select]
+        );
+    }
+
+    #[test]
+    fn rotary_encoder() {
+        let records = [
+            record(EV_REL, REL_X, 1),
+            record(EV_SYN, 0, 0),
+            record(EV_REL, REL_X, -2),
+            // the first position is not a rotation
+            record(EV_ABS, ABS_X, 3),
+            record(EV_ABS, ABS_X, 3),
+            record(EV_ABS, ABS_X, 1),
+            record(EV_ABS, ABS_X, i32::MIN),
+            record(EV_ABS, ABS_X, i32::MAX),
+            record(EV_ABS, ABS_X, i32::MAX - 1),
+        ];
+        assert_eq!(
+            events(&records),
+            vec![
+                NavEvent::Rotate(1),
+                NavEvent::Rotate(-2),
+                NavEvent::Rotate(-2),
+                NavEvent::Rotate(-1)
+            ]
+        );
+    }
+
+    #[test]
+    fn rollover_encoder() {
+        let map = InputMap::empty().rollover_axis(ABS_X, 24);
+        let bytes = [
+            record(EV_ABS, ABS_X, 22),
+            record(EV_ABS, ABS_X, 23),
+            record(EV_ABS, ABS_X, 0),
+            record(EV_ABS, ABS_X, 1),
+            record(EV_ABS, ABS_X, 23),
+            record(EV_ABS, ABS_X, 20),
+        ]
+        .concat();
+        let mut input = InputReader::new(Cursor::new(bytes)).map(map);
+        let events: Vec<NavEvent> = input.events().map(|e| e.unwrap()).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                NavEvent::Rotate(1),
+                NavEvent::Rotate(1),
+                NavEvent::Rotate(1),
+                NavEvent::Rotate(-2),
+                NavEvent::Rotate(-3)
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_map() {
+        let map = InputMap::empty().key(0x100, NavEvent::Back);
+        let bytes = [record(EV_KEY, KEY_UP, 1), record(EV_KEY, 0x100, 1)].concat();
+        let mut input = InputReader::new(Cursor::new(bytes)).map(map);
+
+        assert_eq!(input.read_event().unwrap(), NavEvent::Back);
+        assert_eq!(
+            input.read_event().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn try_read_split_records() {
+        let bytes = [record(EV_KEY, 0x100, 1), record(EV_KEY, KEY_UP, 1)].concat();
+        let (head, tail) = bytes.split_at(INPUT_EVENT_SIZE + 3);
+        let mut input = InputReader::new(Cursor::new(head.to_vec()));
+
+        // nothing to read in the middle of a record
+        assert_eq!(input.try_read_event().unwrap(), None);
+        input.reader = Cursor::new(tail.to_vec());
+        assert_eq!(input.try_read_event().unwrap(), Some(NavEvent::Up));
+        assert_eq!(input.try_read_event().unwrap(), None);
+    }
+
+    #[test]
+    fn keypad_events() {
+        let event = KeyEvent {
+            key: Key::Escape,
+            action: KeyAction::Repeat,
+        };
+        assert_eq!(event.nav_event(), Some(NavEvent::Back));
+
+        let event = KeyEvent {
+            key: Key::Up,
+            action: KeyAction::Release,
+        };
+        assert_eq!(event.nav_event(), None);
+    }
+}
@@ -0,0 +1,334 @@
+//! Read the key events of the keypad exposed by the Linux `panel` driver.
+//!
+//! The `panel` driver that creates the `/dev/lcd` device can also scan a
+//! keypad and create a `/dev/keypad` device. Each key event is reported as a
+//! line of text (e.g. `"Up\n"`), whose content is defined by the keypad
+//! profile selected in the driver.
+//!
+//! A [`KeypadProfile`] maps these lines to typed [`KeyEvent`]s. The default
+//! profile matches the strings of the profiles shipped with the driver.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use charlcd::keypad::{Key, Keypad};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut keypad = Keypad::default()?; // will use "/dev/keypad"
+//!
+//!     for event in keypad.events() {
+//!         let event = event?;
+//!         if event.key == Key::Escape {
+//!             break;
+//!         }
+//!         println!("{:?}", event);
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Result};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_KEYPAD_DEV_PATH: &str = "/dev/keypad";
+
+/// Delay between two reads of a non-blocking device in
+/// [`Keypad::read_event`].
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A key of the keypad.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Escape,
+    Enter,
+    Help,
+    /// A line that is not bound in the keypad profile.
+    Other(String),
+}
+
+/// What happened to a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Press,
+    Repeat,
+    Release,
+}
+
+/// A key event read from the keypad.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub action: KeyAction,
+}
+
+/// Mapping between the lines sent by the driver and key events.
+///
+/// Lines that are not bound are reported as a [`KeyAction::Press`] of a
+/// [`Key::Other`] key.
+#[derive(Clone, Debug)]
+pub struct KeypadProfile {
+    bindings: Vec<(String, KeyEvent)>,
+}
+
+impl Default for KeypadProfile {
+    /// Profile matching the `old`, `new` and `nexcom` profiles of the
+    /// driver.
+    ///
+    /// These profiles send the same line when a key is pressed and when it is
+    /// repeated, and nothing when it is released: every line is reported as a
+    /// [`KeyAction::Press`].
+    fn default() -> Self {
+        KeypadProfile::empty()
+            .bind("Up", Key::Up, KeyAction::Press)
+            .bind("Down", Key::Down, KeyAction::Press)
+            .bind("Left", Key::Left, KeyAction::Press)
+            .bind("Right", Key::Right, KeyAction::Press)
+            .bind("Esc", Key::Escape, KeyAction::Press)
+            .bind("Ret", Key::Enter, KeyAction::Press)
+            .bind("Help", Key::Help, KeyAction::Press)
+    }
+}
+
+impl KeypadProfile {
+    /// Create a profile without any binding.
+    pub fn empty() -> KeypadProfile {
+        KeypadProfile {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Report `line` as the `action` of `key`, replacing any previous binding
+    /// of `line`.
+    ///
+    /// This is useful for drivers patched to send distinct strings for key
+    /// press, repeat and release.
+    pub fn bind(mut self, line: &str, key: Key, action: KeyAction) -> KeypadProfile {
+        self.bindings.retain(|(l, _)| l != line);
+        self.bindings
+            .push((line.to_string(), KeyEvent { key, action }));
+        self
+    }
+
+    /// Decode a line sent by the driver, without its trailing newline.
+    pub fn decode(&self, line: &str) -> KeyEvent {
+        match self.bindings.iter().find(|(l, _)| l == line) {
+            Some((_, event)) => event.clone(),
+            None => KeyEvent {
+                key: Key::Other(line.to_string()),
+                action: KeyAction::Press,
+            },
+        }
+    }
+}
+
+/// A keypad reading key events from the driver (or whatever that implements
+/// the [`Read`] trait).
+pub struct Keypad<R> {
+    reader: R,
+    profile: KeypadProfile,
+    pending: Vec<u8>,
+}
+
+impl<R> Keypad<R>
+where
+    R: Read,
+{
+    /// Create a new [`Keypad`] instance that will read the lines sent by the
+    /// driver from the provided [`Read`], using the default profile.
+    pub fn new(reader: R) -> Keypad<R> {
+        Keypad {
+            reader,
+            profile: KeypadProfile::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Decode the lines with `profile` instead of the default profile.
+    pub fn profile(mut self, profile: KeypadProfile) -> Keypad<R> {
+        self.profile = profile;
+        self
+    }
+
+    /// Wait for the next key event.
+    ///
+    /// An error of kind [`ErrorKind::UnexpectedEof`] is returned if the end of
+    /// the input is reached.
+    pub fn read_event(&mut self) -> Result<KeyEvent> {
+        loop {
+            if let Some(event) = self.pop_event() {
+                return Ok(event);
+            }
+            match self.fill() {
+                Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "keypad closed")),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Get the next key event if one is available, `None` otherwise.
+    ///
+    /// This never blocks as long as the underlying reader is non-blocking,
+    /// which is the case of the device opened by [`Keypad::from_dev_path`].
+    pub fn try_read_event(&mut self) -> Result<Option<KeyEvent>> {
+        loop {
+            if let Some(event) = self.pop_event() {
+                return Ok(Some(event));
+            }
+            match self.fill() {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Iterate over the key events, waiting for each of them, until the end
+    /// of the input.
+    pub fn events(&mut self) -> Events<'_, R> {
+        Events { keypad: self }
+    }
+
+    /// Read the available bytes, returning how many were read.
+    fn fill(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 64];
+        let count = self.reader.read(&mut buf)?;
+        self.pending.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+
+    /// Decode the first complete line of the pending bytes, if any.
+    fn pop_event(&mut self) -> Option<KeyEvent> {
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(&['\n', '\r'][..]);
+            if !line.is_empty() {
+                return Some(self.profile.decode(line));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the key events of a [`Keypad`], see [`Keypad::events`].
+pub struct Events<'a, R> {
+    keypad: &'a mut Keypad<R>,
+}
+
+impl<'a, R> Iterator for Events<'a, R>
+where
+    R: Read,
+{
+    type Item = Result<KeyEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.keypad.read_event() {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            res => Some(res),
+        }
+    }
+}
+
+type FileKeypad = Keypad<File>;
+
+impl FileKeypad {
+    /// Create a Keypad instance based on the passed path to the device.
+    ///
+    /// The device is opened in non-blocking mode, so that
+    /// [`Keypad::try_read_event`] returns immediately.
+    pub fn from_dev_path(path: &Path) -> Result<FileKeypad> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(Keypad::new(file))
+    }
+
+    /// Create a default Keypad instance based on `/dev/keypad` device driver
+    /// path.
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> Result<FileKeypad> {
+        Keypad::from_dev_path(Path::new(DEFAULT_KEYPAD_DEV_PATH))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn press(key: Key) -> KeyEvent {
+        KeyEvent {
+            key,
+            action: KeyAction::Press,
+        }
+    }
+
+    #[test]
+    fn decode_default_profile() {
+        let mut keypad = Keypad::new(Cursor::new(b"Up\nRet\n\nS9\n".to_vec()));
+        let events: Vec<KeyEvent> = keypad.events().map(|e| e.unwrap()).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                press(Key::Up),
+                press(Key::Enter),
+                press(Key::Other("S9".to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_profile() {
+        let profile = KeypadProfile::default()
+            .bind("Up+", Key::Up, KeyAction::Repeat)
+            .bind("Up-", Key::Up, KeyAction::Release);
+        let mut keypad = Keypad::new(Cursor::new(b"Up\nUp+\nUp-\n".to_vec())).profile(profile);
+
+        let actions: Vec<KeyAction> = keypad.events().map(|e| e.unwrap().action).collect();
+        assert_eq!(
+            actions,
+            vec![KeyAction::Press, KeyAction::Repeat, KeyAction::Release]
+        );
+    }
+
+    /// Reader returning its chunks one by one, then `WouldBlock` errors.
+    struct Chunks(Vec<&'static [u8]>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.0.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn non_blocking_partial_lines() {
+        let mut keypad = Keypad::new(Chunks(vec![b"Do", b"wn\nLe"]));
+
+        assert_eq!(keypad.try_read_event().unwrap(), Some(press(Key::Down)));
+        assert_eq!(keypad.try_read_event().unwrap(), None);
+
+        keypad.reader.0.push(b"ft\n");
+        assert_eq!(keypad.read_event().unwrap(), press(Key::Left));
+    }
+}
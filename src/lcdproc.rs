@@ -0,0 +1,1236 @@
+//! Serve the LCDproc protocol to clients written for `LCDd`.
+//!
+//! Many tools (system monitors, music player scripts, ...) show their
+//! information through `LCDd`, the LCDproc server, using a line-based TCP
+//! protocol. A [`Server`] implements this protocol on top of a [`Screen`]:
+//! clients add screens made of widgets, and the server displays the screen
+//! with the highest priority, rotating between the screens of equal
+//! priority.
+//!
+//! The supported widgets are `string`, `title`, `hbar`, `vbar`, `icon`,
+//! `scroller` and `num`. The custom characters needed by the bars, icons and
+//! big numbers of the displayed screen are allocated on the fly; when the 8
+//! slots are not enough, the remaining ones are replaced by built-in
+//! characters.
+//!
+//! A [`Server`] only deals with commands and rendering, whatever the
+//! transport. A [`TcpServer`] serves it to TCP clients without blocking, and
+//! the `charlcd-lcdd` binary runs one on `/dev/lcd` and `/dev/keypad`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::thread;
+//! use std::time::{Duration, Instant};
+//! use charlcd::Screen;
+//! use charlcd::lcdproc::{TcpServer, DEFAULT_PORT};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut server = TcpServer::bind(("127.0.0.1", DEFAULT_PORT), width, height)?;
+//!
+//!     loop {
+//!         server.poll()?;
+//!         if server.server_mut().render(&mut screen, Instant::now())? {
+//!             screen.flush()?;
+//!         }
+//!         thread::sleep(Duration::from_millis(10));
+//!     }
+//! }
+//! ```
+
+use std::io::{ErrorKind, Result, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::bar_graph::{self, CELL_LEVELS};
+use crate::big_digit::{self, BigDigits};
+use crate::connection::Connection;
+use crate::custom_char::{self, mirror_x};
+use crate::frame::Frame;
+use crate::keypad::Key;
+use crate::marquee::{Marquee, MarqueeMode};
+use crate::protocol::{Command, Parser};
+use crate::special_char;
+use crate::Screen;
+
+/// TCP port of `LCDd`, on which clients connect by default.
+pub const DEFAULT_PORT: u16 = 13666;
+
+const SERVER_VERSION: &str = "0.5.9";
+const PROTOCOL_VERSION: &str = "0.4";
+
+/// Width of a character cell, in pixels.
+const CELL_WIDTH: u32 = 5;
+
+/// Time unit of the protocol, used by screen durations and scroller speeds.
+const TICK: Duration = Duration::from_millis(125);
+
+/// Default display duration of a screen, in ticks.
+const DEFAULT_DURATION: u32 = 32;
+
+/// Number of custom character slots.
+const SLOTS: usize = 8;
+
+/// Identifier of a client connected to a [`Server`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+/// Priority classes of the screens, the last one being the highest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Hidden,
+    Background,
+    Info,
+    Foreground,
+    Alert,
+    Input,
+}
+
+impl Priority {
+    fn parse(value: &str) -> Option<Priority> {
+        let priority = match value {
+            "hidden" => Priority::Hidden,
+            "background" => Priority::Background,
+            "info" => Priority::Info,
+            "foreground" => Priority::Foreground,
+            "alert" => Priority::Alert,
+            "input" => Priority::Input,
+            // numeric priorities of the older protocol versions, the lower
+            // the more important
+            value => match value.parse::<u32>().ok()? {
+                0..=64 => Priority::Foreground,
+                65..=191 => Priority::Info,
+                _ => Priority::Background,
+            },
+        };
+        Some(priority)
+    }
+}
+
+/// A character of an icon: either a built-in character, or a custom one
+/// replaced by a built-in character when no slot is left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Icon {
+    fallback: u8,
+    glyph: Option<[u8; 8]>,
+}
+
+const FAST_FORWARD: [u8; 8] = [0x00, 0x14, 0x1a, 0x15, 0x1a, 0x14, 0x00, 0x00];
+const NEXT: [u8; 8] = [0x00, 0x11, 0x19, 0x1d, 0x19, 0x11, 0x00, 0x00];
+
+/// Icon of an `icon` widget, by its protocol name.
+fn icon(name: &str) -> Option<Icon> {
+    let (fallback, glyph) = match name {
+        "BLOCK_FILLED" => (special_char::BLOCK, None),
+        "HEART_OPEN" => (b'-', Some([0x00, 0x0a, 0x15, 0x11, 0x0a, 0x04, 0x00, 0x00])),
+        "HEART_FILLED" => (b'#', Some([0x00, 0x0a, 0x1f, 0x1f, 0x0e, 0x04, 0x00, 0x00])),
+        "ARROW_UP" => (b'^', Some(custom_char::UP_TRIANGLE)),
+        "ARROW_DOWN" => (b'v', Some(custom_char::DOWN_TRIANGLE)),
+        "ARROW_LEFT" | "SELECTOR_AT_RIGHT" => (0x7f, None),
+        "ARROW_RIGHT" | "SELECTOR_AT_LEFT" => (0x7e, None),
+        "CHECKBOX_OFF" => (b'_', Some([0x00, 0x1f, 0x11, 0x11, 0x11, 0x1f, 0x00, 0x00])),
+        "CHECKBOX_ON" => (b'X', Some([0x00, 0x1f, 0x1b, 0x15, 0x1b, 0x1f, 0x00, 0x00])),
+        "CHECKBOX_GRAY" => (b'x', Some([0x00, 0x1f, 0x15, 0x1b, 0x15, 0x1f, 0x00, 0x00])),
+        "ELLIPSIS" => (b'.', Some([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0x00])),
+        "STOP" => (
+            special_char::BLOCK,
+            Some([0x00, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x00, 0x00]),
+        ),
+        "PAUSE" => (b'|', Some([0x00, 0x1b, 0x1b, 0x1b, 0x1b, 0x1b, 0x00, 0x00])),
+        "PLAY" => (0x7e, Some(custom_char::RIGHT_TRIANGLE)),
+        "PLAYR" => (0x7f, Some(custom_char::LEFT_TRIANGLE)),
+        "FF" => (b'>', Some(FAST_FORWARD)),
+        "FR" => (b'<', Some(mirror_x(FAST_FORWARD))),
+        "NEXT" => (b'>', Some(NEXT)),
+        "PREV" => (b'<', Some(mirror_x(NEXT))),
+        "REC" => (b'o', Some([0x00, 0x0e, 0x1f, 0x1f, 0x1f, 0x0e, 0x00, 0x00])),
+        _ => return None,
+    };
+    Some(Icon { fallback, glyph })
+}
+
+/// Name of a keypad key in the protocol.
+pub fn key_name(key: &Key) -> &str {
+    match key {
+        Key::Up => "Up",
+        Key::Down => "Down",
+        Key::Left => "Left",
+        Key::Right => "Right",
+        Key::Escape => "Escape",
+        Key::Enter => "Enter",
+        Key::Help => "Help",
+        Key::Other(name) => name,
+    }
+}
+
+/// Characters of a client string, those out of the character set of the
+/// display being replaced by `?`.
+fn to_bytes(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect()
+}
+
+/// Split a command line into arguments, grouping the words between double
+/// quotes or braces.
+fn tokenize(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut arg = String::new();
+        match c {
+            '"' | '{' => {
+                let end = if c == '"' { '"' } else { '}' };
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('\\') => arg.extend(chars.next()),
+                        Some(c) if c == end => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+            }
+            _ => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    arg.push(c);
+                    chars.next();
+                }
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+fn parse_int<T: std::str::FromStr>(value: &str) -> std::result::Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number \"{}\"", value))
+}
+
+/// Custom characters of the displayed screen, in slot order.
+#[derive(Default)]
+struct Glyphs {
+    slots: Vec<[u8; 8]>,
+}
+
+impl Glyphs {
+    /// Slot holding `glyph`, allocating it if needed.
+    fn slot(&mut self, glyph: [u8; 8]) -> Option<u8> {
+        if let Some(slot) = self.slots.iter().position(|g| *g == glyph) {
+            return Some(slot as u8);
+        }
+        if self.slots.len() < SLOTS {
+            self.slots.push(glyph);
+            return Some(self.slots.len() as u8 - 1);
+        }
+        None
+    }
+
+    /// Put `glyph` at the (`x`, `y`) position of `frame`, or `fallback` if
+    /// no slot is left.
+    fn put(&mut self, frame: &mut Frame, x: u32, y: u32, glyph: [u8; 8], fallback: u8) {
+        frame.set(x, y, self.slot(glyph).unwrap_or(fallback));
+    }
+}
+
+/// Custom characters used by big digits, captured from what they upload.
+fn big_digit_glyphs(big: &BigDigits) -> Vec<[u8; 8]> {
+    let mut screen = Screen::new(Vec::new());
+    big.load_glyphs(&mut screen)
+        .expect("writing to a vector does not fail");
+    Parser::new()
+        .parse(screen.get_ref())
+        .into_iter()
+        .filter_map(|command| match command {
+            Command::Generator(_, glyph) => Some(glyph),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Custom character of a horizontal bar cell filled with `columns` pixel
+/// columns, from the left.
+fn hbar_glyph(columns: u32) -> [u8; 8] {
+    let row = (0b11111 << (CELL_WIDTH - columns)) as u8 & 0b11111;
+    [row; 8]
+}
+
+#[derive(Clone, Debug)]
+enum Widget {
+    String {
+        x: u32,
+        y: u32,
+        text: Vec<u8>,
+    },
+    Title {
+        text: Vec<u8>,
+    },
+    HBar {
+        x: u32,
+        y: u32,
+        length: u32,
+    },
+    VBar {
+        x: u32,
+        y: u32,
+        length: u32,
+    },
+    Icon {
+        x: u32,
+        y: u32,
+        icon: Icon,
+    },
+    Scroller {
+        left: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        direction: char,
+        speed: i32,
+        text: Vec<u8>,
+        // when the text started scrolling, None to restart at the next render
+        since: Option<Instant>,
+    },
+    Num {
+        x: u32,
+        digit: u32,
+    },
+}
+
+impl Widget {
+    fn new(kind: &str) -> std::result::Result<Widget, String> {
+        let widget = match kind {
+            "string" => Widget::String {
+                x: 1,
+                y: 1,
+                text: Vec::new(),
+            },
+            "title" => Widget::Title { text: Vec::new() },
+            "hbar" => Widget::HBar {
+                x: 1,
+                y: 1,
+                length: 0,
+            },
+            "vbar" => Widget::VBar {
+                x: 1,
+                y: 1,
+                length: 0,
+            },
+            "icon" => Widget::Icon {
+                x: 1,
+                y: 1,
+                icon: icon("BLOCK_FILLED").expect("known icon"),
+            },
+            "scroller" => Widget::Scroller {
+                left: 1,
+                top: 1,
+                right: 1,
+                bottom: 1,
+                direction: 'h',
+                speed: 1,
+                text: Vec::new(),
+                since: None,
+            },
+            "num" => Widget::Num { x: 1, digit: 0 },
+            "frame" => return Err("Frame widgets are not supported".to_string()),
+            _ => return Err(format!("Invalid widget type \"{}\"", kind)),
+        };
+        Ok(widget)
+    }
+
+    /// Update the widget from the arguments of `widget_set`, for a `width` x
+    /// `height` display.
+    ///
+    /// Positions past the display are clamped to just outside of it, so that
+    /// drawing the widget never goes through huge areas.
+    fn set(&mut self, args: &[String], width: u32, height: u32) -> std::result::Result<(), String> {
+        let (width, height) = (width.saturating_add(1), height.saturating_add(1));
+        let expected = match self {
+            Widget::Title { .. } => 1,
+            Widget::Num { .. } => 2,
+            Widget::Scroller { .. } => 7,
+            _ => 3,
+        };
+        if args.len() != expected {
+            return Err("Wrong number of arguments".to_string());
+        }
+
+        match self {
+            Widget::String { x, y, text } => {
+                *x = parse_int::<u32>(&args[0])?.min(width);
+                *y = parse_int::<u32>(&args[1])?.min(height);
+                *text = to_bytes(&args[2]);
+            }
+            Widget::Title { text } => *text = to_bytes(&args[0]),
+            Widget::HBar { x, y, length } | Widget::VBar { x, y, length } => {
+                *x = parse_int(&args[0])?;
+                *y = parse_int(&args[1])?;
+                *length = parse_int(&args[2])?;
+            }
+            Widget::Icon {
+                x,
+                y,
+                icon: current,
+            } => {
+                *x = parse_int(&args[0])?;
+                *y = parse_int(&args[1])?;
+                *current = icon(&args[2]).ok_or_else(|| format!("Invalid icon \"{}\"", args[2]))?;
+            }
+            Widget::Scroller {
+                left,
+                top,
+                right,
+                bottom,
+                direction,
+                speed,
+                text,
+                since,
+            } => {
+                *left = parse_int::<u32>(&args[0])?.min(width);
+                *top = parse_int::<u32>(&args[1])?.min(height);
+                *right = parse_int::<u32>(&args[2])?.min(width);
+                *bottom = parse_int::<u32>(&args[3])?.min(height);
+                *direction = match args[4].as_str() {
+                    "h" => 'h',
+                    "v" => 'v',
+                    "m" => 'm',
+                    other => return Err(format!("Invalid direction \"{}\"", other)),
+                };
+                *speed = parse_int(&args[5])?;
+                let new_text = to_bytes(&args[6]);
+                if *text != new_text {
+                    *text = new_text;
+                    *since = None;
+                }
+            }
+            Widget::Num { x, digit } => {
+                *x = parse_int(&args[0])?;
+                *digit = parse_int(&args[1])?;
+                if *digit > 10 {
+                    return Err(format!("Invalid number \"{}\"", args[1]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the widget into `frame`, protocol coordinates starting at 1.
+    fn draw(&mut self, frame: &mut Frame, glyphs: &mut Glyphs, now: Instant) {
+        match self {
+            Widget::String { x, y, text } => {
+                if *x > 0 && *y > 0 {
+                    frame.print(*x - 1, *y - 1, text);
+                }
+            }
+            Widget::Title { text } => {
+                let mut line = b"## ".to_vec();
+                line.extend_from_slice(text);
+                line.push(b' ');
+                line.resize(line.len().max(frame.width() as usize), b'#');
+                frame.print(0, 0, line);
+            }
+            Widget::HBar { x, y, length } => {
+                if *x == 0 || *y == 0 {
+                    return;
+                }
+                let (x, y) = (*x - 1, *y - 1);
+                let full = (*length / CELL_WIDTH).min(frame.width().saturating_sub(x));
+                for i in 0..full {
+                    frame.set(x + i, y, special_char::BLOCK);
+                }
+                let rest = *length % CELL_WIDTH;
+                if rest > 0 && x + full < frame.width() {
+                    glyphs.put(frame, x + full, y, hbar_glyph(rest), b' ');
+                }
+            }
+            Widget::VBar { x, y, length } => {
+                if *x == 0 || *y == 0 {
+                    return;
+                }
+                // the bar grows upwards from the (x, y) cell
+                let (x, bottom) = (*x - 1, *y - 1);
+                let full = *length / CELL_LEVELS;
+                // only the cells of the bar within the frame are drawn
+                let hidden = (bottom + 1).saturating_sub(frame.height());
+                for i in hidden..full.min(bottom + 1) {
+                    frame.set(x, bottom - i, special_char::BLOCK);
+                }
+                let rest = *length % CELL_LEVELS;
+                if rest > 0 && full <= bottom && bottom - full < frame.height() && x < frame.width()
+                {
+                    let glyph = bar_graph::level_glyph(rest as u8);
+                    glyphs.put(frame, x, bottom - full, glyph, b' ');
+                }
+            }
+            Widget::Icon { x, y, icon } => {
+                if *x == 0 || *y == 0 || *x > frame.width() || *y > frame.height() {
+                    return;
+                }
+                match icon.glyph {
+                    Some(glyph) => glyphs.put(frame, *x - 1, *y - 1, glyph, icon.fallback),
+                    None => frame.set(*x - 1, *y - 1, icon.fallback),
+                }
+            }
+            Widget::Scroller {
+                left,
+                top,
+                right,
+                bottom,
+                direction,
+                speed,
+                text,
+                since,
+            } => {
+                if *left == 0 || *top == 0 || right < left || bottom < top {
+                    return;
+                }
+                let width = *right - *left + 1;
+                let elapsed = now.saturating_duration_since(*since.get_or_insert(now));
+                // a positive speed is the number of ticks between two moves,
+                // a negative one the number of moves per tick
+                let (elapsed, step) = match *speed {
+                    0 => (Duration::ZERO, TICK),
+                    speed if speed > 0 => (elapsed, TICK * speed as u32),
+                    speed => (elapsed, TICK / speed.unsigned_abs()),
+                };
+
+                if *direction == 'v' {
+                    let lines: Vec<&[u8]> = text.chunks(width as usize).collect();
+                    let rows = (*bottom - *top + 1) as usize;
+                    let offset = if lines.len() > rows {
+                        (elapsed.as_millis() / step.as_millis().max(1)) as usize % lines.len()
+                    } else {
+                        0
+                    };
+                    for row in 0..rows.min(lines.len()) {
+                        let line = lines[(offset + row) % lines.len()];
+                        frame.print(*left - 1, *top - 1 + row as u32, line);
+                    }
+                } else {
+                    let mode = if *direction == 'm' {
+                        MarqueeMode::Bounce
+                    } else {
+                        MarqueeMode::Loop
+                    };
+                    let marquee = Marquee::new(text.as_slice(), width)
+                        .step(step)
+                        .pause(Duration::ZERO)
+                        .mode(mode);
+                    frame.print(*left - 1, *top - 1, marquee.visible_at(elapsed));
+                }
+            }
+            Widget::Num { x, digit } => {
+                if *x == 0 {
+                    return;
+                }
+                let text = if *digit == 10 {
+                    ":".to_string()
+                } else {
+                    digit.to_string()
+                };
+                if frame.height() < big_digit::MIN_ROWS {
+                    frame.print(*x - 1, 0, text);
+                    return;
+                }
+                let lines = BigDigits::new(frame.height())
+                    .lines(&text)
+                    .expect("digits and colon are supported");
+                for (row, line) in lines.iter().enumerate() {
+                    frame.print(*x - 1, row as u32, line);
+                }
+            }
+        }
+    }
+}
+
+/// A screen added by a client.
+struct ClientScreen {
+    id: String,
+    order: u64,
+    priority: Priority,
+    duration: Duration,
+    backlight: Option<bool>,
+    widgets: Vec<(String, Widget)>,
+}
+
+impl ClientScreen {
+    fn set(&mut self, args: &[String]) -> std::result::Result<(), String> {
+        if args.len() % 2 == 1 {
+            return Err("Wrong number of arguments".to_string());
+        }
+        for pair in args.chunks(2) {
+            let (option, value) = (pair[0].as_str(), pair[1].as_str());
+            match option {
+                "-priority" => {
+                    self.priority = Priority::parse(value)
+                        .ok_or_else(|| format!("Invalid priority \"{}\"", value))?;
+                }
+                "-duration" => self.duration = TICK * parse_int::<u32>(value)?,
+                "-backlight" => {
+                    self.backlight = match value {
+                        "on" => Some(true),
+                        "off" => Some(false),
+                        _ => None,
+                    }
+                }
+                // accepted for compatibility, without effect
+                "-name" | "-heartbeat" | "-wid" | "-hgt" | "-timeout" | "-cursor" | "-cursor_x"
+                | "-cursor_y" => {}
+                _ => return Err(format!("Invalid option \"{}\"", option)),
+            }
+        }
+        Ok(())
+    }
+
+    fn widget_mut(&mut self, id: &str) -> std::result::Result<&mut Widget, String> {
+        self.widgets
+            .iter_mut()
+            .find(|(w, _)| w == id)
+            .map(|(_, widget)| widget)
+            .ok_or_else(|| format!("Invalid widget id \"{}\"", id))
+    }
+}
+
+struct Client {
+    id: ClientId,
+    hello: bool,
+    screens: Vec<ClientScreen>,
+    // reserved keys, and whether they are reserved exclusively
+    keys: Vec<(String, bool)>,
+    output: String,
+}
+
+impl Client {
+    fn screen_mut(&mut self, id: &str) -> std::result::Result<&mut ClientScreen, String> {
+        self.screens
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| format!("Invalid screen id \"{}\"", id))
+    }
+}
+
+/// The screen being displayed.
+struct Visible {
+    client: ClientId,
+    screen: String,
+    since: Instant,
+}
+
+/// State of an LCDproc server: its clients, their screens and what is
+/// displayed.
+pub struct Server {
+    width: u32,
+    height: u32,
+    clients: Vec<Client>,
+    next_client: u64,
+    next_order: u64,
+    visible: Option<Visible>,
+    // what was last sent to the screen, None before the first render
+    displayed: Option<Frame>,
+    loaded: [Option<[u8; 8]>; SLOTS],
+    backlight: Option<bool>,
+}
+
+impl Server {
+    /// Create a new server for a `width` x `height` display.
+    pub fn new(width: u32, height: u32) -> Server {
+        Server {
+            width,
+            height,
+            clients: Vec::new(),
+            next_client: 0,
+            next_order: 0,
+            visible: None,
+            displayed: None,
+            loaded: [None; SLOTS],
+            backlight: None,
+        }
+    }
+
+    /// Register a new client.
+    pub fn connect(&mut self) -> ClientId {
+        let id = ClientId(self.next_client);
+        self.next_client += 1;
+        self.clients.push(Client {
+            id,
+            hello: false,
+            screens: Vec::new(),
+            keys: Vec::new(),
+            output: String::new(),
+        });
+        id
+    }
+
+    /// Forget a client, its screens and its key reservations.
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.clients.retain(|c| c.id != client);
+        if self.visible.as_ref().map(|v| v.client) == Some(client) {
+            self.visible = None;
+        }
+    }
+
+    /// Run a command line sent by `client`, without its trailing newline.
+    ///
+    /// The reply is queued in the output of the client, see
+    /// [`take_output`](Server::take_output). Returns `false` if the client
+    /// asked to close the connection.
+    pub fn command(&mut self, client: ClientId, line: &str) -> bool {
+        let reply = match self.run(client, line) {
+            Ok(Some(reply)) => reply,
+            Ok(None) => return false,
+            Err(message) => format!("huh? {}", message),
+        };
+        self.send(client, &reply);
+        true
+    }
+
+    /// Take the lines waiting to be sent to `client`: replies to its
+    /// commands, key events and `listen`/`ignore` notifications.
+    pub fn take_output(&mut self, client: ClientId) -> String {
+        match self.clients.iter_mut().find(|c| c.id == client) {
+            Some(c) => mem::take(&mut c.output),
+            None => String::new(),
+        }
+    }
+
+    /// The client and identifier of the screen being displayed, if any.
+    pub fn visible(&self) -> Option<(ClientId, &str)> {
+        self.visible.as_ref().map(|v| (v.client, v.screen.as_str()))
+    }
+
+    /// Send a key press to the client that reserved it exclusively, or else
+    /// to the client of the displayed screen if it reserved it.
+    ///
+    /// Returns whether a client received the key.
+    pub fn key(&mut self, key: &str) -> bool {
+        let reserved = |c: &Client, exclusive: bool| {
+            c.keys.iter().any(|(k, e)| k == key && (*e || !exclusive))
+        };
+        let target = self
+            .clients
+            .iter()
+            .find(|c| reserved(c, true))
+            .or_else(|| {
+                let visible = self.visible.as_ref()?.client;
+                self.clients
+                    .iter()
+                    .find(|c| c.id == visible && reserved(c, false))
+            })
+            .map(|c| c.id);
+
+        match target {
+            Some(client) => {
+                self.send(client, &format!("key {}", key));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Select the screen to display at `now`, draw it and send what changed
+    /// to `screen`.
+    ///
+    /// Returns whether something was written to the screen.
+    pub fn render<T: Write>(&mut self, screen: &mut Screen<T>, now: Instant) -> Result<bool> {
+        self.update_visible(now);
+
+        let mut frame = Frame::new(self.width, self.height);
+        let mut glyphs = Glyphs::default();
+        let mut backlight = true;
+        if let Some(current) = self.visible_screen_mut() {
+            backlight = current.backlight.unwrap_or(true);
+
+            // big digits need all the slots, the other widgets fall back to
+            // built-in characters
+            let has_num = current
+                .widgets
+                .iter()
+                .any(|(_, w)| matches!(w, Widget::Num { .. }));
+            if has_num && frame.height() >= big_digit::MIN_ROWS {
+                glyphs.slots = big_digit_glyphs(&BigDigits::new(frame.height()));
+            }
+            for (_, widget) in &mut current.widgets {
+                widget.draw(&mut frame, &mut glyphs, now);
+            }
+        }
+
+        let mut written = false;
+        for (slot, glyph) in glyphs.slots.iter().enumerate() {
+            if self.loaded[slot] != Some(*glyph) {
+                screen.custom_char(slot as u8, *glyph)?;
+                self.loaded[slot] = Some(*glyph);
+                written = true;
+            }
+        }
+        if self.backlight != Some(backlight) {
+            if backlight {
+                screen.backlight_on()?;
+            } else {
+                screen.backlight_off()?;
+            }
+            self.backlight = Some(backlight);
+            written = true;
+        }
+        match &self.displayed {
+            Some(displayed) => written |= frame.render_diff(displayed, screen, 0, 0)? > 0,
+            None => {
+                frame.render(screen, 0, 0)?;
+                written = true;
+            }
+        }
+        self.displayed = Some(frame);
+        Ok(written)
+    }
+
+    fn run(&mut self, id: ClientId, line: &str) -> std::result::Result<Option<String>, String> {
+        let args = tokenize(line)?;
+        let (width, height) = (self.width, self.height);
+        let client = self
+            .clients
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| "Unknown client".to_string())?;
+
+        let command = match args.first() {
+            Some(command) => command.as_str(),
+            None => return Err("Empty command".to_string()),
+        };
+        let args = &args[1..];
+        if !client.hello && command != "hello" {
+            return Err("Client must say \"hello\" first".to_string());
+        }
+
+        match command {
+            "hello" => {
+                client.hello = true;
+                return Ok(Some(format!(
+                    "connect LCDproc {} protocol {} lcd wid {} hgt {} cellwid {} cellhgt {}",
+                    SERVER_VERSION, PROTOCOL_VERSION, width, height, CELL_WIDTH, CELL_LEVELS
+                )));
+            }
+            "bye" => return Ok(None),
+            "noop" => return Ok(Some("noop complete".to_string())),
+            "info" => return Ok(Some("charlcd".to_string())),
+            "client_set" => {}
+            "screen_add" => {
+                let [screen] = args else {
+                    return Err("Wrong number of arguments".to_string());
+                };
+                if client.screens.iter().any(|s| &s.id == screen) {
+                    return Err(format!("Screen \"{}\" already exists", screen));
+                }
+                client.screens.push(ClientScreen {
+                    id: screen.clone(),
+                    order: self.next_order,
+                    priority: Priority::Info,
+                    duration: TICK * DEFAULT_DURATION,
+                    backlight: None,
+                    widgets: Vec::new(),
+                });
+                self.next_order += 1;
+            }
+            "screen_del" => {
+                let [screen] = args else {
+                    return Err("Wrong number of arguments".to_string());
+                };
+                client.screen_mut(screen)?;
+                client.screens.retain(|s| &s.id != screen);
+            }
+            "screen_set" => {
+                let (screen, options) = args
+                    .split_first()
+                    .ok_or_else(|| "Wrong number of arguments".to_string())?;
+                client.screen_mut(screen)?.set(options)?;
+            }
+            "widget_add" => {
+                let (screen, widget, kind) = match args {
+                    [screen, widget, kind] => (screen, widget, kind),
+                    [_, _, _, option, _] if option == "-in" => {
+                        return Err("Frame widgets are not supported".to_string())
+                    }
+                    _ => return Err("Wrong number of arguments".to_string()),
+                };
+                let screen = client.screen_mut(screen)?;
+                if screen.widgets.iter().any(|(w, _)| w == widget) {
+                    return Err(format!("Widget \"{}\" already exists", widget));
+                }
+                screen.widgets.push((widget.clone(), Widget::new(kind)?));
+            }
+            "widget_del" => {
+                let [screen, widget] = args else {
+                    return Err("Wrong number of arguments".to_string());
+                };
+                let screen = client.screen_mut(screen)?;
+                screen.widget_mut(widget)?;
+                screen.widgets.retain(|(w, _)| w != widget);
+            }
+            "widget_set" => {
+                if args.len() < 2 {
+                    return Err("Wrong number of arguments".to_string());
+                }
+                client.screen_mut(&args[0])?.widget_mut(&args[1])?.set(
+                    &args[2..],
+                    width,
+                    height,
+                )?;
+            }
+            "client_add_key" => {
+                let (exclusive, keys) = match args.first().map(String::as_str) {
+                    Some("-exclusively") => (true, &args[1..]),
+                    Some("-shared") => (false, &args[1..]),
+                    _ => (false, args),
+                };
+                for key in keys {
+                    let taken = self.clients.iter().any(|c| {
+                        c.id != id && c.keys.iter().any(|(k, e)| k == key && (*e || exclusive))
+                    });
+                    if taken {
+                        return Err(format!("Could not reserve key \"{}\"", key));
+                    }
+                }
+                let client = self
+                    .clients
+                    .iter_mut()
+                    .find(|c| c.id == id)
+                    .expect("client exists");
+                for key in keys {
+                    client.keys.retain(|(k, _)| k != key);
+                    client.keys.push((key.clone(), exclusive));
+                }
+            }
+            "client_del_key" => client.keys.retain(|(k, _)| !args.contains(k)),
+            _ => return Err(format!("Invalid command \"{}\"", command)),
+        }
+        Ok(Some("success".to_string()))
+    }
+
+    fn send(&mut self, client: ClientId, line: &str) {
+        if let Some(c) = self.clients.iter_mut().find(|c| c.id == client) {
+            c.output.push_str(line);
+            c.output.push('\n');
+        }
+    }
+
+    fn visible_screen_mut(&mut self) -> Option<&mut ClientScreen> {
+        let visible = self.visible.as_ref()?;
+        self.clients
+            .iter_mut()
+            .find(|c| c.id == visible.client)?
+            .screens
+            .iter_mut()
+            .find(|s| s.id == visible.screen)
+    }
+
+    /// Display the screen with the highest priority, rotating between the
+    /// screens of the same priority once their duration is over.
+    fn update_visible(&mut self, now: Instant) {
+        let mut candidates: Vec<(u64, ClientId, &str, Priority, Duration)> = self
+            .clients
+            .iter()
+            .flat_map(|c| {
+                c.screens
+                    .iter()
+                    .map(move |s| (s.order, c.id, s.id.as_str(), s.priority, s.duration))
+            })
+            .filter(|candidate| candidate.3 != Priority::Hidden)
+            .collect();
+        let top = candidates.iter().map(|candidate| candidate.3).max();
+        candidates.retain(|candidate| Some(candidate.3) == top);
+        candidates.sort_by_key(|candidate| candidate.0);
+
+        let current = self.visible.as_ref().and_then(|v| {
+            candidates
+                .iter()
+                .position(|c| c.1 == v.client && c.2 == v.screen)
+                .map(|index| (index, now.saturating_duration_since(v.since)))
+        });
+        let next = match current {
+            Some((index, shown)) if shown < candidates[index].4 || candidates.len() == 1 => return,
+            Some((index, _)) => candidates.get((index + 1) % candidates.len()),
+            None => candidates.first(),
+        };
+        let next = next.map(|c| (c.1, c.2.to_string()));
+
+        if let Some(previous) = self.visible.take() {
+            self.send(previous.client, &format!("ignore {}", previous.screen));
+        }
+        if let Some((client, screen)) = next {
+            self.send(client, &format!("listen {}", screen));
+            self.visible = Some(Visible {
+                client,
+                screen,
+                since: now,
+            });
+        }
+    }
+}
+
+/// A [`Server`] listening for TCP clients.
+///
+/// Nothing blocks: [`poll`](TcpServer::poll) accepts the new clients and
+/// handles what was received, and has to be called regularly along with
+/// [`Server::render`].
+pub struct TcpServer {
+    listener: TcpListener,
+    server: Server,
+    connections: Vec<(ClientId, Connection<TcpStream>)>,
+}
+
+impl TcpServer {
+    /// Listen on `addr` for clients of a `width` x `height` display.
+    pub fn bind<A: ToSocketAddrs>(addr: A, width: u32, height: u32) -> Result<TcpServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpServer {
+            listener,
+            server: Server::new(width, height),
+            connections: Vec::new(),
+        })
+    }
+
+    /// Address the server listens on, e.g. to get the port picked by the
+    /// system when binding port `0`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    pub fn server_mut(&mut self) -> &mut Server {
+        &mut self.server
+    }
+
+    /// Number of connected clients.
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Accept the pending connections, run the commands received and send
+    /// the pending output of every client.
+    ///
+    /// Errors of a connection only close it, only errors of the listening
+    /// socket are returned.
+    pub fn poll(&mut self) -> Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    let client = self.server.connect();
+                    self.connections.push((client, Connection::new(stream)));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        for (client, connection) in &mut self.connections {
+            if !connection.closing {
+                for line in connection.read_lines() {
+                    if !self.server.command(*client, &line) {
+                        connection.closing = true;
+                        break;
+                    }
+                }
+            }
+            connection.send(self.server.take_output(*client).as_bytes());
+        }
+
+        let server = &mut self.server;
+        self.connections.retain(|(client, connection)| {
+            if connection.is_done() {
+                server.disconnect(*client);
+            }
+            !connection.is_done()
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    fn run(server: &mut Server, client: ClientId, commands: &[&str]) -> String {
+        for command in commands {
+            assert!(server.command(client, command));
+        }
+        server.take_output(client)
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(
+            tokenize(r#"widget_set s w 1 2 "say \"hi\"" {a b}"#).unwrap(),
+            vec!["widget_set", "s", "w", "1", "2", "say \"hi\"", "a b"]
+        );
+        assert!(tokenize("widget_set s w 1 2 \"open").is_err());
+    }
+
+    #[test]
+    fn widgets() {
+        let mut server = Server::new(8, 2);
+        let client = server.connect();
+        assert_eq!(
+            run(&mut server, client, &["noop"]),
+            "huh? Client must say \"hello\" first\n"
+        );
+        let output = run(
+            &mut server,
+            client,
+            &[
+                "hello",
+                "screen_add s",
+                "widget_add s t string",
+                "widget_set s t 2 1 {Hi}",
+                "widget_add s b hbar",
+                "widget_set s b 1 2 7",
+                "widget_add s i icon",
+                "widget_set s i 8 2 PLAY",
+                "widget_add s f frame",
+            ],
+        );
+        assert_eq!(
+            output,
+            "connect LCDproc 0.5.9 protocol 0.4 lcd wid 8 hgt 2 cellwid 5 cellhgt 8\n\
+             success\nsuccess\nsuccess\nsuccess\nsuccess\nsuccess\nsuccess\n\
+             huh? Frame widgets are not supported\n"
+        );
+
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        let now = Instant::now();
+        assert!(server.render(&mut screen, now).unwrap());
+        assert!(!server.render(&mut screen, now).unwrap());
+        assert_eq!(
+            buf,
+            b"\x1b[LG01818181818181818;\x1b[LG100080c0e0c080000;\x1b[L+\
+              \x1b[Lx0y0; Hi     \x1b[Lx0y1;\xff\x00     \x01"
+                .to_vec()
+        );
+        assert_eq!(server.take_output(client), "listen s\n");
+    }
+
+    #[test]
+    fn huge_widgets() {
+        let mut server = Server::new(4, 2);
+        let client = server.connect();
+        run(
+            &mut server,
+            client,
+            &[
+                "hello",
+                "screen_add s",
+                "widget_add s h hbar",
+                "widget_set s h 2 1 4294967295",
+                "widget_add s v vbar",
+                "widget_set s v 1 2 4294967295",
+                "widget_add s m scroller",
+                "widget_set s m 2 2 4000000000 4000000000 h 1 {abc}",
+            ],
+        );
+
+        // drawing is bounded by the display
+        let mut buf = Vec::new();
+        server
+            .render(&mut Screen::new(&mut buf), Instant::now())
+            .unwrap();
+        assert!(buf.ends_with(b"\x1b[Lx0y0;\xff\xff\xff\xff\x1b[Lx0y1;\xffabc"));
+    }
+
+    #[test]
+    fn priorities_and_keys() {
+        let mut server = Server::new(16, 2);
+        let (a, b) = (server.connect(), server.connect());
+        run(
+            &mut server,
+            a,
+            &["hello", "screen_add one", "client_add_key Up"],
+        );
+        run(&mut server, b, &["hello", "screen_add two"]);
+        assert_eq!(
+            run(&mut server, b, &["client_add_key -exclusively Up"]),
+            "huh? Could not reserve key \"Up\"\n"
+        );
+
+        let mut screen = Screen::new(Vec::new());
+        let now = Instant::now();
+        server.render(&mut screen, now).unwrap();
+        assert_eq!(server.visible(), Some((a, "one")));
+        assert!(server.key("Up"));
+        assert!(!server.key("Down"));
+        assert_eq!(server.take_output(a), "listen one\nkey Up\n");
+
+        // a higher priority screen is displayed at once
+        run(&mut server, b, &["screen_set two -priority foreground"]);
+        server.render(&mut screen, now).unwrap();
+        assert_eq!(server.visible(), Some((b, "two")));
+        assert!(!server.key("Up"));
+        assert_eq!(server.take_output(a), "ignore one\n");
+        assert_eq!(server.take_output(b), "listen two\n");
+
+        // screens of the same priority rotate
+        run(
+            &mut server,
+            a,
+            &["screen_set one -priority foreground -duration 8"],
+        );
+        server.render(&mut screen, now).unwrap();
+        assert_eq!(server.visible(), Some((b, "two")));
+        server
+            .render(&mut screen, now + Duration::from_secs(4))
+            .unwrap();
+        assert_eq!(server.visible(), Some((a, "one")));
+
+        server.disconnect(a);
+        server
+            .render(&mut screen, now + Duration::from_secs(4))
+            .unwrap();
+        assert_eq!(server.visible(), Some((b, "two")));
+    }
+
+    #[test]
+    fn tcp_client() {
+        let mut server = TcpServer::bind("127.0.0.1:0", 16, 2).unwrap();
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        (&stream).write_all(b"hello\nbye\n").unwrap();
+
+        let mut line = String::new();
+        for _ in 0..500 {
+            server.poll().unwrap();
+            if reader.read_line(&mut line).is_ok() && line.ends_with('\n') {
+                break;
+            }
+        }
+        assert!(line.starts_with("connect LCDproc"));
+
+        for _ in 0..500 {
+            server.poll().unwrap();
+            if server.connections() == 0 {
+                break;
+            }
+        }
+        assert_eq!(server.connections(), 0);
+    }
+}
@@ -1,9 +1,38 @@
 //! A rust crate to interact with the mainline Linux charlcd.c driver.
 
+pub mod bar_graph;
+pub mod big_digit;
+pub mod canvas;
+pub mod carousel;
 mod codes;
+mod connection;
 pub mod custom_char;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+pub mod dialog;
+pub mod frame;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod input;
+pub mod keypad;
+pub mod lcdproc;
+pub mod lock;
+pub mod marquee;
+pub mod menu;
+pub mod notification;
+pub mod number_entry;
 mod of_node;
+pub mod protocol;
+pub mod remote;
+pub mod render_loop;
+pub mod shadow;
+pub mod span;
+pub mod sparkline;
 pub mod special_char;
+pub mod spinner;
+pub mod tee;
+pub mod text_entry;
+pub mod window;
 
 use std::fs::{File, OpenOptions};
 use std::path::Path;
@@ -74,6 +103,21 @@ where
         Screen { writer }
     }
 
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &T {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.writer
+    }
+
+    /// Unwrap this [`Screen`], returning the underlying writer.
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+
     /// Clean the rest of the current line, from current cursor position.
     ///
     /// # Example
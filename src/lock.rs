@@ -0,0 +1,256 @@
+//! Exclusive access to the display shared by several processes.
+//!
+//! Without a daemon, processes writing to `/dev/lcd` at the same time splice
+//! their escape sequences. A [`ScreenLock`] takes an advisory `flock` on the
+//! device, or on a lock file, for the duration of a frame: everything written
+//! through the [`LockGuard`] is flushed before the lock is released.
+//!
+//! The lock is only advisory: every process writing to the display has to
+//! take it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::time::Duration;
+//! use charlcd::Screen;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!
+//!     // give up if another process holds the display for too long
+//!     if let Some(mut frame) = screen.lock_timeout(Duration::from_secs(2))? {
+//!         frame.clear()?;
+//!         frame.write(b"Backup done")?;
+//!         frame.unlock()?;
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Screen;
+
+/// Delay between two attempts to take the lock in
+/// [`ScreenLock::lock_timeout`].
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Apply the `flock` operation `operation` to `file`, retrying when
+/// interrupted.
+fn flock(file: &File, operation: libc::c_int) -> Result<()> {
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let error = Error::last_os_error();
+        if error.kind() != ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Try to take the exclusive lock of `file`, returning whether it was taken.
+fn try_flock(file: &File) -> Result<bool> {
+    match flock(file, libc::LOCK_EX | libc::LOCK_NB) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// An advisory lock guarding the access to a display.
+pub struct ScreenLock {
+    file: File,
+}
+
+impl ScreenLock {
+    /// Lock the device file of `screen` itself.
+    pub fn device(screen: &Screen<BufWriter<File>>) -> Result<ScreenLock> {
+        Ok(ScreenLock {
+            file: screen.get_ref().get_ref().try_clone()?,
+        })
+    }
+
+    /// Lock the file at `path`, created if needed, e.g. when the device
+    /// cannot be opened by every process.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<ScreenLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(ScreenLock { file })
+    }
+
+    /// Wait until the lock is free, then take it to write to `screen`.
+    pub fn lock<'a, T: Write>(&self, screen: &'a mut Screen<T>) -> Result<LockGuard<'a, T>> {
+        let file = self.file.try_clone()?;
+        flock(&file, libc::LOCK_EX)?;
+        Ok(LockGuard { screen, file })
+    }
+
+    /// Take the lock to write to `screen` if it is free, `None` otherwise.
+    pub fn try_lock<'a, T: Write>(
+        &self,
+        screen: &'a mut Screen<T>,
+    ) -> Result<Option<LockGuard<'a, T>>> {
+        let file = self.file.try_clone()?;
+        if try_flock(&file)? {
+            Ok(Some(LockGuard { screen, file }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Wait at most `timeout` for the lock to be free, then take it to write
+    /// to `screen`. Returns `None` if the lock is still held by another
+    /// process after `timeout`.
+    ///
+    /// A `timeout` too large to be reached, such as [`Duration::MAX`], waits
+    /// as long as needed, like [`ScreenLock::lock`].
+    pub fn lock_timeout<'a, T: Write>(
+        &self,
+        screen: &'a mut Screen<T>,
+        timeout: Duration,
+    ) -> Result<Option<LockGuard<'a, T>>> {
+        let deadline = match Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return self.lock(screen).map(Some),
+        };
+        let file = self.file.try_clone()?;
+        loop {
+            if try_flock(&file)? {
+                return Ok(Some(LockGuard { screen, file }));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+/// Exclusive access to a screen, released when dropped.
+///
+/// The screen is flushed before the lock is released, so that what was
+/// written is sent in one go. Use [`LockGuard::unlock`] to get the errors of
+/// the flush, they are ignored when the guard is dropped.
+pub struct LockGuard<'a, T>
+where
+    T: Write,
+{
+    screen: &'a mut Screen<T>,
+    // a duplicate of the locked file, sharing its lock
+    file: File,
+}
+
+impl<'a, T> LockGuard<'a, T>
+where
+    T: Write,
+{
+    /// Flush the screen and release the lock.
+    pub fn unlock(self) -> Result<()> {
+        // the lock itself is released when the guard is dropped
+        self.screen.flush()
+    }
+}
+
+impl<'a, T> Deref for LockGuard<'a, T>
+where
+    T: Write,
+{
+    type Target = Screen<T>;
+
+    fn deref(&self) -> &Screen<T> {
+        self.screen
+    }
+}
+
+impl<'a, T> DerefMut for LockGuard<'a, T>
+where
+    T: Write,
+{
+    fn deref_mut(&mut self) -> &mut Screen<T> {
+        self.screen
+    }
+}
+
+impl<'a, T> Drop for LockGuard<'a, T>
+where
+    T: Write,
+{
+    fn drop(&mut self) {
+        let _ = self.screen.flush();
+        let _ = flock(&self.file, libc::LOCK_UN);
+    }
+}
+
+impl Screen<BufWriter<File>> {
+    /// Wait until no other process holds the lock of the device, then take
+    /// it. See [`ScreenLock`] to lock a separate file instead.
+    pub fn lock(&mut self) -> Result<LockGuard<'_, BufWriter<File>>> {
+        ScreenLock::device(self)?.lock(self)
+    }
+
+    /// Take the lock of the device if no other process holds it, `None`
+    /// otherwise.
+    pub fn try_lock(&mut self) -> Result<Option<LockGuard<'_, BufWriter<File>>>> {
+        ScreenLock::device(self)?.try_lock(self)
+    }
+
+    /// Wait at most `timeout` for the lock of the device, `None` if another
+    /// process still holds it after `timeout`.
+    pub fn lock_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<LockGuard<'_, BufWriter<File>>>> {
+        ScreenLock::device(self)?.lock_timeout(self, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_lock_file() {
+        let path = std::env::temp_dir().join(format!("charlcd-{}.lock", std::process::id()));
+        let (first, second) = (
+            ScreenLock::file(&path).unwrap(),
+            ScreenLock::file(&path).unwrap(),
+        );
+        let mut screen = Screen::new(BufWriter::new(Vec::new()));
+        let mut other = Screen::new(Vec::new());
+
+        let mut guard = first.lock(&mut screen).unwrap();
+        guard.write_all(b"hello").unwrap();
+        assert!(second.try_lock(&mut other).unwrap().is_none());
+        let start = Instant::now();
+        assert!(second
+            .lock_timeout(&mut other, Duration::from_millis(30))
+            .unwrap()
+            .is_none());
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // the screen is flushed when the lock is released
+        drop(guard);
+        assert_eq!(screen.get_ref().get_ref(), b"hello");
+        assert!(second.try_lock(&mut other).unwrap().is_some());
+        assert!(second
+            .lock_timeout(&mut other, Duration::MAX)
+            .unwrap()
+            .is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+//! Scroll a text longer than the display width on a single line.
+//!
+//! Unlike [`Screen::shift_display_left`], which moves every line of the
+//! screen at once, a [`Marquee`] only rewrites its own area of a line with
+//! [`Screen::gotoxy`], so that the other lines stay static.
+//!
+//! The position of the text only depends on the time elapsed since the
+//! marquee started, so it can be drawn as often as wanted: nothing is sent to
+//! the screen if the text did not move since the last draw.
+//!
+//! A [`HardwareMarquee`] is also available: it preloads the text in the
+//! display memory and lets the screen move it with
+//! [`Screen::shift_display_left`], sending a single command per step instead
+//! of a full line rewrite.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::time::{Duration, Instant};
+//! use std::thread;
+//! use charlcd::Screen;
+//! use charlcd::marquee::{Marquee, MarqueeMode};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut title = Marquee::new("Never Gonna Give You Up - Rick Astley", 20)
+//!         .step(Duration::from_millis(250))
+//!         .pause(Duration::from_secs(2))
+//!         .mode(MarqueeMode::Bounce);
+//!
+//!     screen.clear()?;
+//!     screen.write(b"\nstatic second line")?;
+//!
+//!     let start = Instant::now();
+//!     loop {
+//!         if title.draw(&mut screen, 0, 0, start.elapsed())? {
+//!             screen.flush()?;
+//!         }
+//!         thread::sleep(Duration::from_millis(50));
+//!     }
+//! }
+//! ```
+
+use std::io::{Error, ErrorKind, Write};
+use std::time::Duration;
+
+use crate::Screen;
+
+/// How the text moves once its end is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarqueeMode {
+    /// Scroll to the left forever, the start of the text following its end.
+    /// The text pauses every time its start comes back.
+    Loop,
+    /// Scroll to the left until the end of the text is visible, then back to
+    /// the right, and so on.
+    Bounce,
+}
+
+/// A text scrolling horizontally in a fixed width area of a line.
+pub struct Marquee {
+    text: Vec<u8>,
+    width: u32,
+    step: Duration,
+    pause: Duration,
+    mode: MarqueeMode,
+    gap: usize,
+    last_offset: Option<usize>,
+}
+
+impl Marquee {
+    /// Create a new marquee displaying `text` in an area `width` characters
+    /// wide.
+    ///
+    /// By default, the text moves by one character every 300 ms, pauses for
+    /// one second at its start and loops with a gap of 3 spaces.
+    pub fn new<S: AsRef<[u8]>>(text: S, width: u32) -> Marquee {
+        Marquee {
+            text: text.as_ref().to_vec(),
+            width,
+            step: Duration::from_millis(300),
+            pause: Duration::from_secs(1),
+            mode: MarqueeMode::Loop,
+            gap: 3,
+            last_offset: None,
+        }
+    }
+
+    /// Move the text by one character every `step`.
+    pub fn step(mut self, step: Duration) -> Marquee {
+        self.step = step.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Stop the text for `pause` at each of its ends in
+    /// [`MarqueeMode::Bounce`] mode, or at its start on every wrap in
+    /// [`MarqueeMode::Loop`] mode.
+    pub fn pause(mut self, pause: Duration) -> Marquee {
+        self.pause = pause;
+        self
+    }
+
+    /// Select how the text moves once its end is reached.
+    pub fn mode(mut self, mode: MarqueeMode) -> Marquee {
+        self.mode = mode;
+        self
+    }
+
+    /// Number of spaces between the end and the start of the text, in
+    /// [`MarqueeMode::Loop`] mode.
+    pub fn gap(mut self, gap: usize) -> Marquee {
+        self.gap = gap;
+        self
+    }
+
+    /// Replace the displayed text, forcing the next draw.
+    pub fn set_text<S: AsRef<[u8]>>(&mut self, text: S) {
+        self.text = text.as_ref().to_vec();
+        self.last_offset = None;
+    }
+
+    /// Index of the first displayed character of the text, once `elapsed`
+    /// time passed since the marquee started.
+    pub fn offset_at(&self, elapsed: Duration) -> usize {
+        let width = self.width as usize;
+        if self.text.len() <= width {
+            return 0;
+        }
+
+        let pause = self.pause.as_millis();
+        let step = self.step.as_millis();
+
+        match self.mode {
+            MarqueeMode::Loop => {
+                let length = self.text.len() + self.gap;
+                let cycle = pause + length as u128 * step;
+                let t = elapsed.as_millis() % cycle;
+                if t < pause {
+                    0
+                } else {
+                    ((t - pause) / step) as usize % length
+                }
+            }
+            MarqueeMode::Bounce => {
+                let max = self.text.len() - width;
+                let travel = max as u128 * step;
+                let cycle = 2 * (pause + travel);
+                let t = elapsed.as_millis() % cycle;
+                if t < pause {
+                    0
+                } else if t < pause + travel {
+                    ((t - pause) / step) as usize
+                } else if t < 2 * pause + travel {
+                    max
+                } else {
+                    max - ((t - 2 * pause - travel) / step) as usize
+                }
+            }
+        }
+    }
+
+    /// Characters displayed once `elapsed` time passed since the marquee
+    /// started, padded with spaces to the marquee width.
+    pub fn visible_at(&self, elapsed: Duration) -> Vec<u8> {
+        let width = self.width as usize;
+        let offset = self.offset_at(elapsed);
+
+        let mut visible: Vec<u8> = if self.mode == MarqueeMode::Loop && self.text.len() > width {
+            self.text
+                .iter()
+                .copied()
+                .chain(vec![b' '; self.gap])
+                .cycle()
+                .skip(offset)
+                .take(width)
+                .collect()
+        } else {
+            self.text.iter().copied().skip(offset).take(width).collect()
+        };
+        visible.resize(width, b' ');
+        visible
+    }
+
+    /// Draw the marquee starting at the (`x`, `y`) position, once `elapsed`
+    /// time passed since it started.
+    ///
+    /// Returns `true` if something was written to the screen, that is if the
+    /// text moved since the last draw.
+    pub fn draw<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+        elapsed: Duration,
+    ) -> std::io::Result<bool> {
+        let offset = self.offset_at(elapsed);
+        if self.last_offset == Some(offset) {
+            return Ok(false);
+        }
+
+        screen.gotoxy(x, y)?;
+        screen.write_all(&self.visible_at(elapsed))?;
+        self.last_offset = Some(offset);
+        Ok(true)
+    }
+}
+
+/// Number of characters of display memory (DDRAM) for each line.
+pub const DDRAM_LINE_WIDTH: u32 = 40;
+
+/// Number of lines of display memory (DDRAM).
+pub const DDRAM_LINES: u32 = 2;
+
+/// A marquee moving the whole display with hardware shifts.
+///
+/// The HD44780 has 40 characters of display memory per line, of which only
+/// the first 16 or 20 are visible. Up to 40 characters per line are preloaded
+/// in this memory, then each step of the animation is a single
+/// [`Screen::shift_display_left`] command. As the display memory is circular,
+/// the text loops by itself.
+///
+/// The shift applies to every line at once, and moves the visible columns in
+/// the display memory: once shifted, use [`HardwareMarquee::gotoxy`] instead
+/// of [`Screen::gotoxy`] to address the visible columns.
+///
+/// Note: on 4 lines screens, the 3rd and 4th lines are the continuation of the
+/// 1st and 2nd lines in the display memory (e.g. columns 20 to 39 on a 20x4
+/// screen). They are thus scrolled along with the first two lines, and only
+/// these first two lines can be loaded.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::io::Write;
+/// use std::time::{Duration, Instant};
+/// use std::thread;
+/// use charlcd::Screen;
+/// use charlcd::marquee::HardwareMarquee;
+///
+/// fn main() -> std::io::Result<()> {
+///     let mut screen = Screen::default()?;
+///     let mut marquee = HardwareMarquee::new().step(Duration::from_millis(400));
+///
+///     screen.clear()?;
+///     marquee.load_line(&mut screen, 0, "A title that does not fit in 16 chars")?;
+///     marquee.load_line(&mut screen, 1, "and its subtitle")?;
+///
+///     let start = Instant::now();
+///     loop {
+///         if marquee.update(&mut screen, start.elapsed())? {
+///             screen.flush()?;
+///         }
+///         thread::sleep(Duration::from_millis(50));
+///     }
+/// }
+/// ```
+pub struct HardwareMarquee {
+    step: Duration,
+    offset: u32,
+}
+
+impl Default for HardwareMarquee {
+    fn default() -> Self {
+        HardwareMarquee::new()
+    }
+}
+
+impl HardwareMarquee {
+    /// Create a new hardware marquee, considering that the display is not
+    /// shifted yet.
+    ///
+    /// By default, [`HardwareMarquee::update`] moves the display by one
+    /// character every 300 ms.
+    pub fn new() -> HardwareMarquee {
+        HardwareMarquee {
+            step: Duration::from_millis(300),
+            offset: 0,
+        }
+    }
+
+    /// Move the display by one character every `step` in
+    /// [`HardwareMarquee::update`].
+    pub fn step(mut self, step: Duration) -> HardwareMarquee {
+        self.step = step.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Number of characters the display is currently shifted to the left, from
+    /// `0` to `39`.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Load `text` in the whole display memory of line `y` (`0` or `1`).
+    ///
+    /// The text is truncated to 40 characters, or padded with spaces up to 40
+    /// characters so that the loop is seamless.
+    pub fn load_line<T: Write, S: AsRef<[u8]>>(
+        &mut self,
+        screen: &mut Screen<T>,
+        y: u32,
+        text: S,
+    ) -> std::io::Result<()> {
+        if y >= DDRAM_LINES {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("display memory has only {} lines", DDRAM_LINES),
+            ));
+        }
+
+        let mut line: Vec<u8> = text.as_ref().to_vec();
+        line.resize(DDRAM_LINE_WIDTH as usize, b' ');
+
+        // the goto position is a display memory address, not affected by the
+        // display shift
+        screen.gotoxy(0, y)?;
+        screen.write_all(&line)
+    }
+
+    /// Move the cursor to the visible column `x` of line `y`, taking the
+    /// current shift into account.
+    pub fn gotoxy<T: Write>(&self, screen: &mut Screen<T>, x: u32, y: u32) -> std::io::Result<()> {
+        screen.gotoxy((x + self.offset) % DDRAM_LINE_WIDTH, y)
+    }
+
+    /// Shift the display one character to the left, making the text move to
+    /// the left.
+    pub fn step_left<T: Write>(&mut self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.shift_display_left()?;
+        self.offset = (self.offset + 1) % DDRAM_LINE_WIDTH;
+        Ok(())
+    }
+
+    /// Shift the display one character to the right, making the text move to
+    /// the right.
+    pub fn step_right<T: Write>(&mut self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.shift_display_right()?;
+        self.offset = (self.offset + DDRAM_LINE_WIDTH - 1) % DDRAM_LINE_WIDTH;
+        Ok(())
+    }
+
+    /// Shift the display until it is at the `offset` position, using the
+    /// shortest way.
+    pub fn shift_to<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        offset: u32,
+    ) -> std::io::Result<()> {
+        let offset = offset % DDRAM_LINE_WIDTH;
+        let left = (offset + DDRAM_LINE_WIDTH - self.offset) % DDRAM_LINE_WIDTH;
+        if left <= DDRAM_LINE_WIDTH / 2 {
+            for _ in 0..left {
+                self.step_left(screen)?;
+            }
+        } else {
+            for _ in 0..DDRAM_LINE_WIDTH - left {
+                self.step_right(screen)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shift the display back to its original position.
+    pub fn reset<T: Write>(&mut self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        self.shift_to(screen, 0)
+    }
+
+    /// Shift the display to its position once `elapsed` time passed since the
+    /// marquee started.
+    ///
+    /// Returns `true` if something was written to the screen.
+    pub fn update<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        elapsed: Duration,
+    ) -> std::io::Result<bool> {
+        let target = (elapsed.as_millis() / self.step.as_millis()) % DDRAM_LINE_WIDTH as u128;
+        if target as u32 == self.offset {
+            return Ok(false);
+        }
+        self.shift_to(screen, target as u32)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn short_text_does_not_move() {
+        let marquee = Marquee::new("abc", 5);
+        assert_eq!(marquee.offset_at(ms(10_000)), 0);
+        assert_eq!(marquee.visible_at(ms(10_000)), b"abc  ");
+    }
+
+    #[test]
+    fn loop_mode() {
+        let marquee = Marquee::new("abcdef", 4)
+            .step(ms(100))
+            .pause(ms(500))
+            .gap(1);
+
+        assert_eq!(marquee.visible_at(ms(0)), b"abcd");
+        assert_eq!(marquee.visible_at(ms(499)), b"abcd");
+        assert_eq!(marquee.visible_at(ms(600)), b"bcde");
+        assert_eq!(marquee.visible_at(ms(1000)), b"f ab");
+        assert_eq!(marquee.visible_at(ms(1100)), b" abc");
+        assert_eq!(marquee.visible_at(ms(1200)), b"abcd"); // pause again
+        assert_eq!(marquee.visible_at(ms(1699)), b"abcd");
+        assert_eq!(marquee.visible_at(ms(1800)), b"bcde");
+        assert_eq!(marquee.visible_at(ms(3600)), b"abcd"); // and on every wrap
+        assert_eq!(marquee.visible_at(ms(4200)), b"bcde");
+    }
+
+    #[test]
+    fn bounce_mode() {
+        let marquee = Marquee::new("abcdef", 4)
+            .step(ms(100))
+            .pause(ms(500))
+            .mode(MarqueeMode::Bounce);
+
+        assert_eq!(marquee.offset_at(ms(0)), 0);
+        assert_eq!(marquee.offset_at(ms(600)), 1);
+        assert_eq!(marquee.offset_at(ms(700)), 2);
+        assert_eq!(marquee.offset_at(ms(1100)), 2); // pause at the end
+        assert_eq!(marquee.offset_at(ms(1300)), 1);
+        assert_eq!(marquee.offset_at(ms(1400)), 0);
+        assert_eq!(marquee.offset_at(ms(1400 + 500)), 0);
+    }
+
+    #[test]
+    fn draw_only_when_moved() {
+        let mut marquee = Marquee::new("abcdef", 4).step(ms(100)).pause(ms(0));
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+
+        assert!(marquee.draw(&mut screen, 2, 1, ms(0)).unwrap());
+        assert!(!marquee.draw(&mut screen, 2, 1, ms(50)).unwrap());
+        assert!(marquee.draw(&mut screen, 2, 1, ms(100)).unwrap());
+
+        assert_eq!(buf, b"\x1b[Lx2y1;abcd\x1b[Lx2y1;bcde");
+    }
+
+    #[test]
+    fn hardware_load_line() {
+        let mut marquee = HardwareMarquee::new();
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+
+        marquee.load_line(&mut screen, 1, "abc").unwrap();
+        let err = marquee.load_line(&mut screen, 2, "abc").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let mut expected = b"\x1b[Lx0y1;abc".to_vec();
+        expected.resize(expected.len() + 37, b' ');
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn hardware_offset_tracking() {
+        let mut marquee = HardwareMarquee::new().step(ms(100));
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+
+        assert!(!marquee.update(&mut screen, ms(50)).unwrap());
+        assert!(marquee.update(&mut screen, ms(250)).unwrap());
+        assert_eq!(marquee.offset(), 2);
+        marquee.gotoxy(&mut screen, 39, 0).unwrap();
+
+        marquee.shift_to(&mut screen, 39).unwrap(); // shortest way is right
+        assert_eq!(marquee.offset(), 39);
+        marquee.reset(&mut screen).unwrap();
+        assert_eq!(marquee.offset(), 0);
+
+        assert_eq!(
+            buf,
+            b"\x1b[LL\x1b[LL\x1b[Lx1y0;\x1b[LR\x1b[LR\x1b[LR\x1b[LL"
+        );
+    }
+}
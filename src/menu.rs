@@ -0,0 +1,663 @@
+//! Hierarchical menus driven by navigation events.
+//!
+//! A [`Menu`] is a tree of items: actions, toggles, value editors and nested
+//! submenus. A [`MenuView`] displays a menu fitted to the screen geometry,
+//! with a selection marker and a scrollbar, and is fed with abstract
+//! [`NavEvent`]s so that any input source can drive it (see the
+//! [`input`][crate::input] and [`keypad`][crate::keypad] modules).
+//!
+//! The view never runs any code by itself: selecting an action, changing a
+//! toggle or a value is reported as a [`MenuEvent`] for the application to
+//! handle.
+//!
+//! With the `config` feature, menu trees can also be loaded from TOML or JSON
+//! files, see the `config` module.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use charlcd::Screen;
+//! use charlcd::input::InputReader;
+//! use charlcd::menu::{Menu, MenuEvent, MenuItem, MenuView};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut input = InputReader::from_dev_path(Path::new("/dev/input/event0"))?;
+//!
+//!     let network = Menu::new()
+//!         .item(MenuItem::toggle("DHCP", "dhcp", true))
+//!         .item(MenuItem::value("MTU", "mtu", 1500, 576, 9000, 4));
+//!     let root = Menu::new()
+//!         .item(MenuItem::submenu("Network", network))
+//!         .item(MenuItem::value("Contrast", "contrast", 5, 0, 10, 1))
+//!         .item(MenuItem::action("Reboot", "reboot"));
+//!
+//!     let mut view = MenuView::new(root, screen.width()?, screen.height()?);
+//!     view.load_glyphs(&mut screen)?;
+//!
+//!     loop {
+//!         view.render(&mut screen, 0, 0)?;
+//!         screen.flush()?;
+//!
+//!         match view.handle(input.read_event()?) {
+//!             Some(MenuEvent::Action(id)) if id == "reboot" => break,
+//!             Some(MenuEvent::Exit) => break,
+//!             Some(event) => println!("{:?}", event),
+//!             None => {}
+//!         }
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+#[cfg(feature = "config")]
+pub mod config;
+
+use std::io::Write;
+
+use crate::custom_char;
+use crate::input::NavEvent;
+use crate::Screen;
+
+/// Number of custom characters slots used by a [`MenuView`].
+pub const GLYPH_COUNT: u8 = 3;
+
+/// │
+#[rustfmt::skip]
+const SCROLL_TRACK: [u8; 8] = [
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+];
+
+/// ┃
+#[rustfmt::skip]
+const SCROLL_THUMB: [u8; 8] = [
+    0b01110,
+    0b01110,
+    0b01110,
+    0b01110,
+    0b01110,
+    0b01110,
+    0b01110,
+    0b01110,
+];
+
+/// What an item does when it is selected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemKind {
+    /// Open a nested menu.
+    Submenu(Menu),
+    /// Report [`MenuEvent::Action`] with the given identifier.
+    Action(String),
+    /// Report [`MenuEvent::Command`] with the given shell command.
+    Command(String),
+    /// Flip a named boolean setting.
+    Toggle { setting: String, value: bool },
+    /// Edit a named integer setting, between `min` and `max` by `step`.
+    /// The bounds can be in any order.
+    Value {
+        setting: String,
+        value: i32,
+        min: i32,
+        max: i32,
+        step: i32,
+    },
+}
+
+impl ItemKind {
+    /// Name of the setting bound to a toggle or value item.
+    pub fn setting(&self) -> Option<&str> {
+        match self {
+            ItemKind::Toggle { setting, .. } | ItemKind::Value { setting, .. } => Some(setting),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of a [`Menu`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuItem {
+    pub label: String,
+    pub kind: ItemKind,
+}
+
+impl MenuItem {
+    /// Create an item opening the `menu` submenu.
+    pub fn submenu(label: &str, menu: Menu) -> MenuItem {
+        MenuItem {
+            label: label.to_string(),
+            kind: ItemKind::Submenu(menu),
+        }
+    }
+
+    /// Create an item reporting the `id` action when selected.
+    pub fn action(label: &str, id: &str) -> MenuItem {
+        MenuItem {
+            label: label.to_string(),
+            kind: ItemKind::Action(id.to_string()),
+        }
+    }
+
+    /// Create an item reporting the `command` shell command when selected.
+    pub fn command(label: &str, command: &str) -> MenuItem {
+        MenuItem {
+            label: label.to_string(),
+            kind: ItemKind::Command(command.to_string()),
+        }
+    }
+
+    /// Create an item flipping the `setting` boolean, initially `value`.
+    pub fn toggle(label: &str, setting: &str, value: bool) -> MenuItem {
+        MenuItem {
+            label: label.to_string(),
+            kind: ItemKind::Toggle {
+                setting: setting.to_string(),
+                value,
+            },
+        }
+    }
+
+    /// Create an item editing the `setting` integer, initially `value`.
+    /// The bounds `min` and `max` can be given in any order.
+    pub fn value(
+        label: &str,
+        setting: &str,
+        value: i32,
+        min: i32,
+        max: i32,
+        step: i32,
+    ) -> MenuItem {
+        let (min, max) = bounds(min, max);
+        MenuItem {
+            label: label.to_string(),
+            kind: ItemKind::Value {
+                setting: setting.to_string(),
+                value: value.clamp(min, max),
+                min,
+                max,
+                step: step.max(1),
+            },
+        }
+    }
+
+    /// Text displayed on the right side of the item.
+    fn status(&self, editing: bool) -> String {
+        match &self.kind {
+            ItemKind::Toggle { value: true, .. } => "on".to_string(),
+            ItemKind::Toggle { value: false, .. } => "off".to_string(),
+            ItemKind::Value { value, .. } if editing => format!("[{}]", value),
+            ItemKind::Value { value, .. } => value.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// A list of items.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Menu {
+    pub items: Vec<MenuItem>,
+}
+
+impl Menu {
+    /// Create an empty menu.
+    pub fn new() -> Menu {
+        Menu { items: Vec::new() }
+    }
+
+    /// Append `item` to the menu.
+    pub fn item(mut self, item: MenuItem) -> Menu {
+        self.items.push(item);
+        self
+    }
+
+    /// Get the first toggle or value item bound to `setting`, in this menu
+    /// or in its submenus.
+    fn setting(&self, setting: &str) -> Option<&ItemKind> {
+        self.items.iter().find_map(|item| match &item.kind {
+            ItemKind::Submenu(menu) => menu.setting(setting),
+            kind if kind.setting() == Some(setting) => Some(kind),
+            _ => None,
+        })
+    }
+
+    /// Find the first toggle or value item bound to `setting`, in this menu
+    /// or in its submenus.
+    fn find_setting(&mut self, setting: &str) -> Option<&mut ItemKind> {
+        for item in self.items.iter_mut() {
+            let found = match &mut item.kind {
+                ItemKind::Submenu(menu) => menu.find_setting(setting),
+                kind if kind.setting() == Some(setting) => Some(kind),
+                _ => None,
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+/// What happened in the menu after a navigation event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuEvent {
+    /// An action item was selected.
+    Action(String),
+    /// A command item was selected. The command is not run by the view.
+    Command(String),
+    /// A toggle item changed to the given value.
+    Toggled(String, bool),
+    /// A value item was edited and confirmed with the given value.
+    ValueChanged(String, i32),
+    /// Back was requested from the root menu.
+    Exit,
+}
+
+/// Position in an opened menu.
+#[derive(Clone, Copy, Debug, Default)]
+struct Level {
+    selected: usize,
+    scroll: usize,
+}
+
+/// A menu displayed on a screen area.
+pub struct MenuView {
+    root: Menu,
+    width: u32,
+    height: u32,
+    first_slot: u8,
+    // index of the opened submenu at each level, and position in each level
+    path: Vec<usize>,
+    levels: Vec<Level>,
+    // value of the item being edited before the edition started
+    editing: Option<i32>,
+}
+
+impl MenuView {
+    /// Create a new view of `root` taking `width` x `height` characters, with
+    /// the first item of the root menu selected.
+    ///
+    /// The view uses the custom characters slots `0` to `2`.
+    pub fn new(root: Menu, width: u32, height: u32) -> MenuView {
+        MenuView {
+            root,
+            width,
+            height: height.max(1),
+            first_slot: 0,
+            path: Vec::new(),
+            levels: vec![Level::default()],
+            editing: None,
+        }
+    }
+
+    /// Use the [`GLYPH_COUNT`] custom characters slots starting from `slot`
+    /// instead of `0`.
+    pub fn first_slot(mut self, slot: u8) -> MenuView {
+        self.first_slot = slot;
+        self
+    }
+
+    /// Store the marker and scrollbar characters into the screen memory.
+    pub fn load_glyphs<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.custom_char(self.first_slot, custom_char::RIGHT_TRIANGLE)?;
+        screen.custom_char(self.first_slot + 1, SCROLL_TRACK)?;
+        screen.custom_char(self.first_slot + 2, SCROLL_THUMB)?;
+        Ok(())
+    }
+
+    /// The whole menu tree, with the current state of its settings.
+    pub fn root(&self) -> &Menu {
+        &self.root
+    }
+
+    /// Depth of the opened submenu, `0` for the root menu.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Index of the selected item in the opened menu.
+    pub fn selected(&self) -> usize {
+        self.level().selected
+    }
+
+    /// Whether a value item is being edited.
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Current state of the toggle bound to `setting`.
+    pub fn toggle(&self, setting: &str) -> Option<bool> {
+        match self.root.setting(setting) {
+            Some(ItemKind::Toggle { value, .. }) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Current value of the value item bound to `setting`.
+    pub fn value(&self, setting: &str) -> Option<i32> {
+        match self.root.setting(setting) {
+            Some(ItemKind::Value { value, .. }) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Change the state of the toggle bound to `setting`, e.g. to reflect a
+    /// change made outside of the menu.
+    pub fn set_toggle(&mut self, setting: &str, state: bool) {
+        if let Some(ItemKind::Toggle { value, .. }) = self.root.find_setting(setting) {
+            *value = state;
+        }
+    }
+
+    /// Change the value of the value item bound to `setting`, clamped to its
+    /// bounds.
+    pub fn set_value(&mut self, setting: &str, new: i32) {
+        if let Some(ItemKind::Value {
+            value, min, max, ..
+        }) = self.root.find_setting(setting)
+        {
+            let (min, max) = bounds(*min, *max);
+            *value = new.clamp(min, max);
+        }
+    }
+
+    /// Update the view according to a navigation event.
+    ///
+    /// Up and down (or a rotation) move the selection, select (or right)
+    /// activates the selected item and back (or left) closes the opened
+    /// submenu. While a value is being edited, up and down change the value,
+    /// select confirms it and back restores the previous value.
+    pub fn handle(&mut self, event: NavEvent) -> Option<MenuEvent> {
+        if let Some(original) = self.editing {
+            return self.handle_edit(event, original);
+        }
+
+        match event {
+            NavEvent::Up => self.move_selection(-1),
+            NavEvent::Down => self.move_selection(1),
+            NavEvent::Rotate(steps) => self.move_selection(steps),
+            NavEvent::Select | NavEvent::Right => return self.activate(),
+            NavEvent::Back | NavEvent::Left => {
+                if self.path.pop().is_none() {
+                    return Some(MenuEvent::Exit);
+                }
+                self.levels.pop();
+            }
+        }
+        None
+    }
+
+    /// Draw the whole view with its top-left corner at the (`x`, `y`)
+    /// position.
+    pub fn render<T: Write>(&self, screen: &mut Screen<T>, x: u32, y: u32) -> std::io::Result<()> {
+        let menu = self.menu();
+        let level = self.level();
+        let count = menu.items.len();
+        let scrollbar = count > self.height as usize;
+        let width = self.width as usize;
+
+        for row in 0..self.height as usize {
+            let index = level.scroll + row;
+            let mut line = Vec::with_capacity(width);
+
+            if let Some(item) = menu.items.get(index) {
+                let selected = index == level.selected;
+                line.push(if selected { self.first_slot } else { b' ' });
+
+                let status = item.status(selected && self.is_editing());
+                let room = width.saturating_sub(1 + scrollbar as usize + status.len());
+                line.extend(item.label.bytes().take(room));
+                line.resize(room + 1, b' ');
+                line.extend(status.bytes());
+            }
+
+            line.resize(width.saturating_sub(scrollbar as usize), b' ');
+            if scrollbar {
+                let thumb = level.selected * (self.height as usize - 1) / (count - 1);
+                line.push(self.first_slot + if row == thumb { 2 } else { 1 });
+            }
+            line.truncate(width);
+
+            screen.gotoxy(x, y + row as u32)?;
+            screen.write_all(&line)?;
+        }
+        Ok(())
+    }
+
+    fn menu(&self) -> &Menu {
+        let mut menu = &self.root;
+        for &index in &self.path {
+            menu = match &menu.items[index].kind {
+                ItemKind::Submenu(sub) => sub,
+                _ => unreachable!("only submenus are opened"),
+            };
+        }
+        menu
+    }
+
+    fn menu_mut(&mut self) -> &mut Menu {
+        let mut menu = &mut self.root;
+        for &index in &self.path {
+            menu = match &mut menu.items[index].kind {
+                ItemKind::Submenu(sub) => sub,
+                _ => unreachable!("only submenus are opened"),
+            };
+        }
+        menu
+    }
+
+    fn level(&self) -> Level {
+        *self.levels.last().expect("root level is never closed")
+    }
+
+    fn move_selection(&mut self, steps: i32) {
+        let count = self.menu().items.len();
+        if count == 0 {
+            return;
+        }
+        let height = self.height as usize;
+        let level = self.levels.last_mut().expect("root level is never closed");
+
+        let selected = (level.selected as i64 + steps as i64).clamp(0, count as i64 - 1);
+        level.selected = selected as usize;
+
+        // keep the selected item visible
+        if level.selected < level.scroll {
+            level.scroll = level.selected;
+        } else if level.selected >= level.scroll + height {
+            level.scroll = level.selected + 1 - height;
+        }
+    }
+
+    fn activate(&mut self) -> Option<MenuEvent> {
+        let selected = self.selected();
+        let item = self.menu_mut().items.get_mut(selected)?;
+
+        match &mut item.kind {
+            ItemKind::Submenu(_) => {
+                self.path.push(selected);
+                self.levels.push(Level::default());
+                None
+            }
+            ItemKind::Action(id) => Some(MenuEvent::Action(id.clone())),
+            ItemKind::Command(command) => Some(MenuEvent::Command(command.clone())),
+            ItemKind::Toggle { setting, value } => {
+                *value = !*value;
+                Some(MenuEvent::Toggled(setting.clone(), *value))
+            }
+            ItemKind::Value { value, .. } => {
+                self.editing = Some(*value);
+                None
+            }
+        }
+    }
+
+    fn handle_edit(&mut self, event: NavEvent, original: i32) -> Option<MenuEvent> {
+        let selected = self.selected();
+        let item = &mut self.menu_mut().items[selected];
+        let (setting, value, min, max, step) = match &mut item.kind {
+            ItemKind::Value {
+                setting,
+                value,
+                min,
+                max,
+                step,
+            } => (setting.clone(), value, *min, *max, *step),
+            _ => unreachable!("only value items are edited"),
+        };
+
+        let steps = match event {
+            NavEvent::Up | NavEvent::Right => 1,
+            NavEvent::Down | NavEvent::Left => -1,
+            NavEvent::Rotate(steps) => steps,
+            NavEvent::Select => {
+                let confirmed = *value;
+                self.editing = None;
+                return Some(MenuEvent::ValueChanged(setting, confirmed));
+            }
+            NavEvent::Back => {
+                *value = original;
+                self.editing = None;
+                return None;
+            }
+        };
+        let (min, max) = bounds(min, max);
+        *value = (*value as i64 + steps as i64 * step as i64).clamp(min as i64, max as i64) as i32;
+        None
+    }
+}
+
+/// Bounds of a value item, lowest first.
+fn bounds(min: i32, max: i32) -> (i32, i32) {
+    (min.min(max), min.max(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu() -> Menu {
+        let sub = Menu::new()
+            .item(MenuItem::toggle("DHCP", "dhcp", true))
+            .item(MenuItem::value("MTU", "mtu", 1500, 1000, 1600, 100));
+        Menu::new()
+            .item(MenuItem::submenu("Network", sub))
+            .item(MenuItem::action("Reboot", "reboot"))
+            .item(MenuItem::action("Halt", "halt"))
+    }
+
+    fn render(view: &MenuView) -> Vec<u8> {
+        let mut buf = Vec::new();
+        view.render(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        buf
+    }
+
+    #[test]
+    fn navigation() {
+        let mut view = MenuView::new(menu(), 16, 2);
+
+        assert_eq!(view.handle(NavEvent::Up), None);
+        assert_eq!(view.selected(), 0);
+        view.handle(NavEvent::Rotate(5));
+        assert_eq!(view.selected(), 2);
+        assert_eq!(
+            view.handle(NavEvent::Select),
+            Some(MenuEvent::Action("halt".to_string()))
+        );
+
+        view.handle(NavEvent::Rotate(-2));
+        view.handle(NavEvent::Select);
+        assert_eq!(view.depth(), 1);
+        assert_eq!(
+            view.handle(NavEvent::Select),
+            Some(MenuEvent::Toggled("dhcp".to_string(), false))
+        );
+        assert_eq!(view.toggle("dhcp"), Some(false));
+
+        view.handle(NavEvent::Back);
+        assert_eq!(view.depth(), 0);
+        assert_eq!(view.handle(NavEvent::Back), Some(MenuEvent::Exit));
+    }
+
+    #[test]
+    fn value_edition() {
+        let mut view = MenuView::new(menu(), 16, 2);
+        view.handle(NavEvent::Select);
+        view.handle(NavEvent::Down);
+        view.handle(NavEvent::Select);
+        assert!(view.is_editing());
+
+        view.handle(NavEvent::Up);
+        view.handle(NavEvent::Up);
+        assert_eq!(view.value("mtu"), Some(1600)); // clamped
+        view.handle(NavEvent::Back);
+        assert_eq!(view.value("mtu"), Some(1500));
+
+        view.handle(NavEvent::Select);
+        view.handle(NavEvent::Rotate(-2));
+        assert_eq!(
+            view.handle(NavEvent::Select),
+            Some(MenuEvent::ValueChanged("mtu".to_string(), 1300))
+        );
+        assert!(!view.is_editing());
+
+        // bounds given in reverse order
+        let reversed = Menu::new().item(MenuItem::value("Level", "level", 50, 10, 0, 1));
+        assert_eq!(MenuView::new(reversed, 16, 2).value("level"), Some(10));
+
+        // including in items built without the constructor
+        let item = MenuItem {
+            label: "Level".to_string(),
+            kind: ItemKind::Value {
+                setting: "level".to_string(),
+                value: 5,
+                min: 10,
+                max: 0,
+                step: 1,
+            },
+        };
+        let mut view = MenuView::new(Menu::new().item(item), 16, 2);
+        view.set_value("level", 20);
+        assert_eq!(view.value("level"), Some(10));
+        view.handle(NavEvent::Select);
+        view.handle(NavEvent::Rotate(-15));
+        assert_eq!(
+            view.handle(NavEvent::Select),
+            Some(MenuEvent::ValueChanged("level".to_string(), 0))
+        );
+    }
+
+    #[test]
+    fn render_with_scrollbar() {
+        let mut view = MenuView::new(menu(), 10, 2);
+        view.handle(NavEvent::Down);
+        view.handle(NavEvent::Down);
+
+        // "Reboot" and "Halt" visible, "Halt" selected, thumb at the bottom
+        assert_eq!(
+            render(&view),
+            b"\x1b[Lx0y0; Reboot  \x01\x1b[Lx0y1;\x00Halt    \x02".to_vec()
+        );
+    }
+
+    #[test]
+    fn render_status() {
+        let mut view = MenuView::new(menu(), 12, 2);
+        view.handle(NavEvent::Select);
+        view.handle(NavEvent::Down);
+        view.handle(NavEvent::Select);
+
+        assert_eq!(
+            render(&view),
+            b"\x1b[Lx0y0; DHCP     on\x1b[Lx0y1;\x00MTU  [1500]".to_vec()
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Load menu trees from TOML or JSON files.
+//!
+//! This allows to change the front panel menus without recompiling the
+//! application that owns the screen. Each item has a `label` and exactly one
+//! of the following keys:
+//!
+//! - `items`: the items of a submenu;
+//! - `command`: a shell command, reported as [`MenuEvent::Command`];
+//! - `callback`: an identifier, reported as [`MenuEvent::Action`];
+//! - `toggle`: the name of a boolean setting, with an optional boolean
+//!   `default`;
+//! - `value`: the name of an integer setting, with `min` and `max` bounds and
+//!   optional `step` and `default`.
+//!
+//! The tree is validated at load time: labels must be ASCII and fit in the
+//! screen width along with the selection marker, the scrollbar and the
+//! setting value, and each setting can only be bound to a single item.
+//!
+//! # Example
+//!
+//! ```toml
+//! [[items]]
+//! label = "Network"
+//!
+//!   [[items.items]]
+//!   label = "DHCP"
+//!   toggle = "dhcp"
+//!   default = true
+//!
+//! [[items]]
+//! label = "Contrast"
+//! value = "contrast"
+//! min = 0
+//! max = 10
+//! default = 5
+//!
+//! [[items]]
+//! label = "Reboot"
+//! command = "systemctl reboot"
+//! ```
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use charlcd::Screen;
+//! use charlcd::menu::{config, MenuEvent, MenuView};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let width = screen.width()?;
+//!
+//!     let root = config::load(Path::new("/etc/front-panel.toml"), width)?;
+//!     let mut view = MenuView::new(root, width, screen.height()?);
+//!
+//!     // ... feed the view with navigation events, then:
+//!     if let Some(MenuEvent::Command(command)) = view.handle(charlcd::input::NavEvent::Select) {
+//!         config::run_command(&command)?;
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`MenuEvent::Command`]: super::MenuEvent::Command
+//! [`MenuEvent::Action`]: super::MenuEvent::Action
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use serde::Deserialize;
+
+use super::{ItemKind, Menu, MenuItem};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MenuConfig {
+    items: Vec<ItemConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DefaultConfig {
+    Bool(bool),
+    Int(i32),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemConfig {
+    label: String,
+    items: Option<Vec<ItemConfig>>,
+    command: Option<String>,
+    callback: Option<String>,
+    toggle: Option<String>,
+    value: Option<String>,
+    min: Option<i32>,
+    max: Option<i32>,
+    step: Option<i32>,
+    default: Option<DefaultConfig>,
+}
+
+/// Load a menu tree from a TOML or JSON file, depending on its extension
+/// (`.toml` or `.json`), validated for a screen `width` characters wide.
+pub fn load(path: &Path, width: u32) -> Result<Menu> {
+    let content = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => from_toml(&content, width),
+        Some("json") => from_json(&content, width),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{}: unknown menu file format", path.display()),
+        )),
+    }
+}
+
+/// Load a menu tree from TOML, validated for a screen `width` characters
+/// wide.
+pub fn from_toml(content: &str, width: u32) -> Result<Menu> {
+    let config: MenuConfig =
+        toml::from_str(content).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+    build_menu(config.items, width, "", &mut HashSet::new())
+}
+
+/// Load a menu tree from JSON, validated for a screen `width` characters
+/// wide.
+pub fn from_json(content: &str, width: u32) -> Result<Menu> {
+    let config: MenuConfig = serde_json::from_str(content)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+    build_menu(config.items, width, "", &mut HashSet::new())
+}
+
+/// Run a command reported by [`MenuEvent::Command`] with `sh -c`, waiting for
+/// its completion.
+///
+/// [`MenuEvent::Command`]: super::MenuEvent::Command
+pub fn run_command(command: &str) -> Result<ExitStatus> {
+    Command::new("sh").arg("-c").arg(command).status()
+}
+
+fn invalid(path: &str, message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: {}", path, message))
+}
+
+/// Build the menu of `items`, adding the settings bound to its items to
+/// `settings`.
+fn build_menu(
+    items: Vec<ItemConfig>,
+    width: u32,
+    parent: &str,
+    settings: &mut HashSet<String>,
+) -> Result<Menu> {
+    let mut menu = Menu::new();
+    for item in items {
+        menu = menu.item(build_item(item, width, parent, settings)?);
+    }
+    Ok(menu)
+}
+
+fn build_item(
+    config: ItemConfig,
+    width: u32,
+    parent: &str,
+    settings: &mut HashSet<String>,
+) -> Result<MenuItem> {
+    let path = if parent.is_empty() {
+        config.label.clone()
+    } else {
+        format!("{} > {}", parent, config.label)
+    };
+
+    let kinds = [
+        config.items.is_some(),
+        config.command.is_some(),
+        config.callback.is_some(),
+        config.toggle.is_some(),
+        config.value.is_some(),
+    ];
+    if kinds.iter().filter(|&&k| k).count() != 1 {
+        return Err(invalid(
+            &path,
+            "expected exactly one of items, command, callback, toggle or value".to_string(),
+        ));
+    }
+    if (config.min.is_some() || config.max.is_some() || config.step.is_some())
+        && config.value.is_none()
+    {
+        return Err(invalid(
+            &path,
+            "min, max and step are only valid for values".to_string(),
+        ));
+    }
+    if config.default.is_some() && config.toggle.is_none() && config.value.is_none() {
+        return Err(invalid(
+            &path,
+            "default is only valid for toggles and values".to_string(),
+        ));
+    }
+    if let Some(setting) = config.toggle.as_ref().or(config.value.as_ref()) {
+        if !settings.insert(setting.clone()) {
+            return Err(invalid(
+                &path,
+                format!("setting {} is already bound to another item", setting),
+            ));
+        }
+    }
+
+    let kind = if let Some(items) = config.items {
+        ItemKind::Submenu(build_menu(items, width, &path, settings)?)
+    } else if let Some(command) = config.command {
+        ItemKind::Command(command)
+    } else if let Some(callback) = config.callback {
+        ItemKind::Action(callback)
+    } else if let Some(setting) = config.toggle {
+        let value = match config.default {
+            None => false,
+            Some(DefaultConfig::Bool(value)) => value,
+            Some(DefaultConfig::Int(_)) => {
+                return Err(invalid(
+                    &path,
+                    "toggle default must be a boolean".to_string(),
+                ))
+            }
+        };
+        ItemKind::Toggle { setting, value }
+    } else if let Some(setting) = config.value {
+        let (min, max) = match (config.min, config.max) {
+            (Some(min), Some(max)) if min <= max => (min, max),
+            (Some(_), Some(_)) => {
+                return Err(invalid(&path, "min is greater than max".to_string()))
+            }
+            _ => return Err(invalid(&path, "values need min and max".to_string())),
+        };
+        let step = config.step.unwrap_or(1);
+        if step <= 0 {
+            return Err(invalid(&path, "step must be positive".to_string()));
+        }
+        let value = match config.default {
+            None => min,
+            Some(DefaultConfig::Int(value)) if (min..=max).contains(&value) => value,
+            Some(_) => {
+                return Err(invalid(
+                    &path,
+                    format!("default must be an integer between {} and {}", min, max),
+                ))
+            }
+        };
+        ItemKind::Value {
+            setting,
+            value,
+            min,
+            max,
+            step,
+        }
+    } else {
+        unreachable!("exactly one kind is set");
+    };
+
+    let item = MenuItem {
+        label: config.label,
+        kind,
+    };
+    validate_label(&item, width, &path)?;
+    Ok(item)
+}
+
+/// Check that the label of `item` fits in the screen width.
+fn validate_label(item: &MenuItem, width: u32, path: &str) -> Result<()> {
+    if !item.label.is_ascii() {
+        return Err(invalid(path, "label must be ASCII".to_string()));
+    }
+
+    // widest text displayed on the right side of the item, and its separator
+    let status = match &item.kind {
+        ItemKind::Toggle { .. } => " off".len(),
+        ItemKind::Value { min, max, .. } => {
+            let widest = min.to_string().len().max(max.to_string().len());
+            " []".len() + widest
+        }
+        _ => 0,
+    };
+
+    // selection marker and scrollbar
+    let room = (width as usize).saturating_sub(2 + status);
+    if item.label.len() > room {
+        return Err(invalid(
+            path,
+            format!(
+                "label is {} characters long, only {} fit in a {} characters wide screen",
+                item.label.len(),
+                room,
+                width
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        [[items]]
+        label = "Network"
+
+          [[items.items]]
+          label = "DHCP"
+          toggle = "dhcp"
+          default = true
+
+        [[items]]
+        label = "Contrast"
+        value = "contrast"
+        min = 0
+        max = 10
+        default = 5
+
+        [[items]]
+        label = "Reboot"
+        command = "systemctl reboot"
+
+        [[items]]
+        label = "Play"
+        callback = "play"
+    "#;
+
+    #[test]
+    fn toml_menu() {
+        let menu = from_toml(TOML, 16).unwrap();
+        let expected = Menu::new()
+            .item(MenuItem::submenu(
+                "Network",
+                Menu::new().item(MenuItem::toggle("DHCP", "dhcp", true)),
+            ))
+            .item(MenuItem::value("Contrast", "contrast", 5, 0, 10, 1))
+            .item(MenuItem::command("Reboot", "systemctl reboot"))
+            .item(MenuItem::action("Play", "play"));
+
+        assert_eq!(menu, expected);
+    }
+
+    #[test]
+    fn json_menu() {
+        let json = r#"{"items": [
+            {"label": "Wi-Fi", "items": [{"label": "Enabled", "toggle": "wifi"}]}
+        ]}"#;
+        let menu = from_json(json, 16).unwrap();
+
+        assert_eq!(
+            menu,
+            Menu::new().item(MenuItem::submenu(
+                "Wi-Fi",
+                Menu::new().item(MenuItem::toggle("Enabled", "wifi", false))
+            ))
+        );
+    }
+
+    #[test]
+    fn label_too_long() {
+        let err = from_toml(TOML, 12).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("Contrast: "));
+
+        let json =
+            r#"{"items": [{"label": "Net", "items": [{"label": "Enabled", "toggle": "w"}]}]}"#;
+        let err = from_json(json, 10).unwrap_err();
+        assert!(err.to_string().starts_with("Net > Enabled: "));
+    }
+
+    #[test]
+    fn invalid_items() {
+        let both = r#"{"items": [{"label": "A", "callback": "a", "command": "a"}]}"#;
+        assert!(from_json(both, 16).is_err());
+
+        let none = r#"{"items": [{"label": "A"}]}"#;
+        assert!(from_json(none, 16).is_err());
+
+        let bounds = r#"{"items": [{"label": "A", "value": "a", "min": 5, "max": 1}]}"#;
+        assert!(from_json(bounds, 16).is_err());
+
+        let default =
+            r#"{"items": [{"label": "A", "value": "a", "min": 0, "max": 1, "default": 2}]}"#;
+        assert!(from_json(default, 16).is_err());
+
+        let unknown = r#"{"items": [{"label": "A", "callback": "a", "colour": "red"}]}"#;
+        assert!(from_json(unknown, 16).is_err());
+    }
+
+    #[test]
+    fn misplaced_default() {
+        let err = from_json(
+            r#"{"items": [{"label": "A", "callback": "a", "default": 1}]}"#,
+            16,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "A: default is only valid for toggles and values"
+        );
+
+        let submenu = r#"{"items": [{"label": "A", "items": [], "default": true}]}"#;
+        assert!(from_json(submenu, 16).is_err());
+    }
+
+    #[test]
+    fn duplicate_settings() {
+        let toml = r#"
+            [[items]]
+            label = "DHCP"
+            toggle = "dhcp"
+
+            [[items]]
+            label = "Network"
+
+              [[items.items]]
+              label = "Mode"
+              value = "dhcp"
+              min = 0
+              max = 2
+        "#;
+        let err = from_toml(toml, 16).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Network > Mode: setting dhcp is already bound to another item"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Show notifications over the current content of the display.
+//!
+//! A [`NotificationQueue`] collects the messages posted by the different
+//! parts of an application. The notification with the highest priority is
+//! displayed over whatever is on the screen, preempting a lower priority one
+//! if needed, and the previous content is restored when no notification is
+//! left.
+//!
+//! Like dialogs, notifications need a [`Shadow`] screen to save and restore
+//! the display (see [`Screen::shadowed`]). The application should not draw
+//! while a notification is displayed, see [`NotificationQueue::is_active`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::thread;
+//! use std::time::{Duration, Instant};
+//! use charlcd::Screen;
+//! use charlcd::notification::{Notification, NotificationQueue};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut screen = screen.shadowed(width, height);
+//!     let mut queue = NotificationQueue::new();
+//!
+//!     screen.write(b"Temp 21.5")?;
+//!     queue.push(Notification::new("Backup done", Duration::from_secs(3)));
+//!     queue.push(
+//!         Notification::new("Fan failure!", Duration::from_secs(10))
+//!             .priority(10)
+//!             .flash_backlight(),
+//!     );
+//!
+//!     while queue.update(&mut screen, Instant::now())? {
+//!         screen.flush()?;
+//!         thread::sleep(Duration::from_millis(100));
+//!     }
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::time::{Duration, Instant};
+
+use crate::dialog::{self, Dialog, MessageBox};
+use crate::shadow::{Shadow, Snapshot};
+use crate::Screen;
+
+/// A message shown for some time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    text: String,
+    duration: Duration,
+    priority: u8,
+    flash: bool,
+}
+
+impl Notification {
+    /// Create a new notification showing `text` for `duration`, with the
+    /// lowest priority.
+    pub fn new(text: &str, duration: Duration) -> Notification {
+        Notification {
+            text: text.to_string(),
+            duration,
+            priority: 0,
+            flash: false,
+        }
+    }
+
+    /// Preempt the notifications with a lower priority.
+    pub fn priority(mut self, priority: u8) -> Notification {
+        self.priority = priority;
+        self
+    }
+
+    /// Flash the backlight when the notification is displayed.
+    pub fn flash_backlight(mut self) -> Notification {
+        self.flash = true;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// The notification being displayed.
+struct Active {
+    notification: Notification,
+    order: u64,
+    shown_at: Instant,
+}
+
+impl Active {
+    fn remaining(&self, now: Instant) -> Duration {
+        self.notification
+            .duration
+            .saturating_sub(now.saturating_duration_since(self.shown_at))
+    }
+}
+
+/// Notifications waiting to be displayed over the screen content.
+pub struct NotificationQueue {
+    // notifications and their posting order, to keep the queue fair among
+    // notifications of the same priority
+    pending: Vec<(u64, Notification)>,
+    next_order: u64,
+    active: Option<Active>,
+    saved: Option<Snapshot>,
+}
+
+impl Default for NotificationQueue {
+    fn default() -> Self {
+        NotificationQueue::new()
+    }
+}
+
+impl NotificationQueue {
+    /// Create a new empty queue.
+    pub fn new() -> NotificationQueue {
+        NotificationQueue {
+            pending: Vec::new(),
+            next_order: 0,
+            active: None,
+            saved: None,
+        }
+    }
+
+    /// Post a notification, displayed at the next [`update`] if it has the
+    /// highest priority.
+    ///
+    /// [`update`]: NotificationQueue::update
+    pub fn push(&mut self, notification: Notification) {
+        self.pending.push((self.next_order, notification));
+        self.next_order += 1;
+    }
+
+    /// Number of notifications waiting to be displayed, the displayed one
+    /// excluded.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Whether a notification is displayed over the screen content.
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// The notification being displayed, if any.
+    pub fn current(&self) -> Option<&Notification> {
+        self.active.as_ref().map(|a| &a.notification)
+    }
+
+    /// End the display of the current notification at the next
+    /// [`update`](NotificationQueue::update).
+    pub fn dismiss(&mut self) {
+        if let Some(active) = &mut self.active {
+            active.notification.duration = Duration::ZERO;
+        }
+    }
+
+    /// Display the notification that should be displayed at `now`: expire the
+    /// current one, preempt it with a higher priority one or restore the
+    /// screen content when the queue is empty.
+    ///
+    /// Returns whether a notification is displayed.
+    pub fn update<W: Write>(
+        &mut self,
+        screen: &mut Screen<Shadow<W>>,
+        now: Instant,
+    ) -> Result<bool> {
+        if let Some(active) = &self.active {
+            if active.remaining(now).is_zero() {
+                self.active = None;
+            }
+        }
+
+        let preempt = match (&self.active, self.highest()) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(active), Some(index)) => {
+                self.pending[index].1.priority > active.notification.priority
+            }
+        };
+        if preempt {
+            // the preempted notification will be displayed again for the time
+            // it had left
+            if let Some(mut active) = self.active.take() {
+                active.notification.duration = active.remaining(now);
+                self.pending.push((active.order, active.notification));
+            }
+            let index = self.highest().expect("a notification is pending");
+            let (order, notification) = self.pending.remove(index);
+            self.show(screen, order, notification, now)?;
+        }
+
+        if self.active.is_none() {
+            if let Some(saved) = self.saved.take() {
+                saved.restore(screen)?;
+            }
+        }
+        Ok(self.active.is_some())
+    }
+
+    /// Index of the pending notification with the highest priority, the
+    /// oldest first.
+    fn highest(&self) -> Option<usize> {
+        self.pending
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (order, n))| (n.priority, std::cmp::Reverse(*order)))
+            .map(|(index, _)| index)
+    }
+
+    fn show<W: Write>(
+        &mut self,
+        screen: &mut Screen<Shadow<W>>,
+        order: u64,
+        notification: Notification,
+        now: Instant,
+    ) -> Result<()> {
+        if self.saved.is_none() {
+            self.saved = Some(dialog::take_over(screen)?);
+        }
+        MessageBox::new(&notification.text).render(screen)?;
+        if notification.flash {
+            screen.flash_backlight()?;
+        }
+        self.active = Some(Active {
+            notification,
+            order,
+            shown_at: now,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Screen<Shadow<Vec<u8>>> {
+        Screen::new(Vec::new()).shadowed(8, 1)
+    }
+
+    fn line(screen: &Screen<Shadow<Vec<u8>>>) -> &[u8] {
+        screen.get_ref().snapshot().line(0)
+    }
+
+    #[test]
+    fn expire_and_restore() {
+        let mut screen = screen();
+        screen.write_all(b"21.5").unwrap();
+        let start = Instant::now();
+
+        let mut queue = NotificationQueue::new();
+        queue.push(Notification::new("one", Duration::from_secs(2)));
+        queue.push(Notification::new("two", Duration::from_secs(2)));
+
+        assert!(queue.update(&mut screen, start).unwrap());
+        assert_eq!(line(&screen), b"  one   ");
+        assert!(queue
+            .update(&mut screen, start + Duration::from_secs(2))
+            .unwrap());
+        assert_eq!(line(&screen), b"  two   ");
+        assert!(!queue
+            .update(&mut screen, start + Duration::from_secs(4))
+            .unwrap());
+        assert_eq!(line(&screen), b"21.5    ");
+        assert!(!queue.is_active());
+    }
+
+    #[test]
+    fn preemption() {
+        let mut screen = screen();
+        let start = Instant::now();
+
+        let mut queue = NotificationQueue::new();
+        queue.push(Notification::new("low", Duration::from_secs(5)));
+        queue.update(&mut screen, start).unwrap();
+
+        queue.push(
+            Notification::new("high", Duration::from_secs(1))
+                .priority(1)
+                .flash_backlight(),
+        );
+        queue
+            .update(&mut screen, start + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(queue.current().map(Notification::text), Some("high"));
+        assert!(screen.get_ref().get_ref().ends_with(b"\x1b[L*"));
+
+        // the low priority notification gets its 3 remaining seconds back
+        queue
+            .update(&mut screen, start + Duration::from_secs(3))
+            .unwrap();
+        assert_eq!(line(&screen), b"  low   ");
+        assert!(queue
+            .update(&mut screen, start + Duration::from_secs(5))
+            .unwrap());
+        assert!(!queue
+            .update(&mut screen, start + Duration::from_secs(6))
+            .unwrap());
+    }
+}
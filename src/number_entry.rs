@@ -0,0 +1,276 @@
+//! Edit integers and fixed-point decimals in place.
+//!
+//! A [`NumberEntry`] displays a number with an optional unit and edits it
+//! with navigation events: up and down (or a rotary encoder) change the digit
+//! under the cursor, left and right move the cursor between digits. On the
+//! last digit, the value changes by the configured step, which makes the
+//! entry behave as a spinner when the cursor is not moved.
+//!
+//! Decimals are handled as fixed-point numbers: with `2` decimals, the value
+//! `1234` is displayed as `12.34`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use charlcd::{special_char, Screen};
+//! use charlcd::input::{InputReader, Outcome};
+//! use charlcd::number_entry::NumberEntry;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut input = InputReader::from_dev_path(Path::new("/dev/input/event0"))?;
+//!
+//!     // setpoint between 5.0°C and 35.0°C, by steps of 0.5°C
+//!     let mut entry = NumberEntry::new(215, 50, 350)
+//!         .decimals(1)
+//!         .step(5)
+//!         .unit(&[special_char::DEGREE, b'C']);
+//!
+//!     let setpoint = loop {
+//!         entry.render(&mut screen, 0, 1)?;
+//!         screen.flush()?;
+//!
+//!         if let Some(outcome) = entry.handle(input.read_event()?) {
+//!             break outcome;
+//!         }
+//!     };
+//!     entry.hide_cursor(&mut screen)?;
+//!
+//!     if let Outcome::Confirmed(setpoint) = setpoint {
+//!         println!("{}", setpoint as f64 / 10.0);
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Write;
+
+use crate::input::{NavEvent, Outcome};
+use crate::Screen;
+
+/// A number input field.
+pub struct NumberEntry {
+    value: i64,
+    min: i64,
+    max: i64,
+    step: i64,
+    decimals: u32,
+    unit: Vec<u8>,
+    // position of the edited digit, 0 for the last one
+    digit: u32,
+}
+
+impl NumberEntry {
+    /// Create a new field editing `value` between `min` and `max`, by steps
+    /// of `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn new(value: i64, min: i64, max: i64) -> NumberEntry {
+        assert!(min <= max, "min must not be greater than max");
+        NumberEntry {
+            value: value.clamp(min, max),
+            min,
+            max,
+            step: 1,
+            decimals: 0,
+            unit: Vec::new(),
+            digit: 0,
+        }
+    }
+
+    /// Display the value as a fixed-point number with `decimals` digits after
+    /// the decimal point. The value, bounds and step are still expressed in
+    /// units of the last digit.
+    pub fn decimals(mut self, decimals: u32) -> NumberEntry {
+        self.decimals = decimals;
+        self
+    }
+
+    /// Change the value by `step` when the last digit is edited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is not positive.
+    pub fn step(mut self, step: i64) -> NumberEntry {
+        assert!(step > 0, "step must be positive");
+        self.step = step;
+        self
+    }
+
+    /// Display `unit` after the value, e.g. `&[special_char::MU, b'F']` or
+    /// `&[b'k', special_char::OMEGA]`.
+    pub fn unit(mut self, unit: &[u8]) -> NumberEntry {
+        self.unit = unit.to_vec();
+        self
+    }
+
+    /// Current value, in units of the last digit.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Current value as a floating point number.
+    pub fn to_f64(&self) -> f64 {
+        self.value as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// Position of the edited digit, `0` for the last one.
+    pub fn digit(&self) -> u32 {
+        self.digit
+    }
+
+    /// Number of characters taken by the field, unit included.
+    pub fn width(&self) -> u32 {
+        (self.min < 0) as u32 + self.digits() + (self.decimals > 0) as u32 + self.unit.len() as u32
+    }
+
+    /// Update the field according to a navigation event.
+    ///
+    /// Up and down (or a rotation) change the edited digit, left and right
+    /// select the digit to edit, select confirms the value and back cancels
+    /// the edition.
+    pub fn handle(&mut self, event: NavEvent) -> Option<Outcome<i64>> {
+        match event {
+            NavEvent::Up => self.change(1),
+            NavEvent::Down => self.change(-1),
+            NavEvent::Rotate(steps) => self.change(steps as i64),
+            NavEvent::Left => self.digit = (self.digit + 1).min(self.digits() - 1),
+            NavEvent::Right => self.digit = self.digit.saturating_sub(1),
+            NavEvent::Select => return Some(Outcome::Confirmed(self.value)),
+            NavEvent::Back => return Some(Outcome::Cancelled),
+        }
+        None
+    }
+
+    /// Draw the field at the (`x`, `y`) position, then move the cursor to the
+    /// edited digit and make it visible.
+    pub fn render<T: Write>(&self, screen: &mut Screen<T>, x: u32, y: u32) -> std::io::Result<()> {
+        let digits = self.digits() as usize;
+        let integer = digits - self.decimals as usize;
+
+        let mut line = Vec::with_capacity(self.width() as usize);
+        if self.min < 0 {
+            line.push(b' ');
+        }
+        let sign = line.len();
+
+        let formatted = format!("{:0width$}", self.value.unsigned_abs(), width = digits);
+        for (i, c) in formatted.bytes().enumerate() {
+            if i == integer {
+                line.push(b'.');
+            }
+            // blank the leading zeros of the integer part
+            let leading = line[sign..].iter().all(|&c| c == b' ');
+            line.push(if c == b'0' && leading && i + 1 < integer {
+                b' '
+            } else {
+                c
+            });
+        }
+        if self.value < 0 {
+            let first = line.iter().position(|&c| c != b' ').unwrap_or(line.len());
+            line[first - 1] = b'-';
+        }
+        line.extend_from_slice(&self.unit);
+
+        // column of the edited digit, skipping the decimal point
+        let index = digits - 1 - self.digit as usize;
+        let column = sign + index + (index >= integer) as usize;
+
+        screen.gotoxy(x, y)?;
+        screen.write_all(&line)?;
+        screen.gotoxy(x + column as u32, y)?;
+        screen.cursor_on()?;
+        screen.blink_on()?;
+        Ok(())
+    }
+
+    /// Hide the cursor shown by [`NumberEntry::render`].
+    pub fn hide_cursor<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.cursor_off()?;
+        screen.blink_off()?;
+        Ok(())
+    }
+
+    /// Number of digits needed by the widest bound, at least one before the
+    /// decimal point.
+    fn digits(&self) -> u32 {
+        let widest = self.min.unsigned_abs().max(self.max.unsigned_abs());
+        (widest.checked_ilog10().unwrap_or(0) + 1).max(self.decimals + 1)
+    }
+
+    fn change(&mut self, steps: i64) {
+        let increment = match self.digit {
+            0 => self.step,
+            digit => 10i64.saturating_pow(digit).max(self.step),
+        };
+        self.value = self
+            .value
+            .saturating_add(steps.saturating_mul(increment))
+            .clamp(self.min, self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::special_char;
+
+    fn render(entry: &NumberEntry) -> Vec<u8> {
+        let mut buf = Vec::new();
+        entry.render(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        buf
+    }
+
+    #[test]
+    fn edit_digits() {
+        let mut entry = NumberEntry::new(215, 50, 350).decimals(1).step(5);
+
+        entry.handle(NavEvent::Up);
+        assert_eq!(entry.value(), 220);
+        entry.handle(NavEvent::Left);
+        entry.handle(NavEvent::Rotate(3));
+        assert_eq!(entry.value(), 250);
+        entry.handle(NavEvent::Left);
+        entry.handle(NavEvent::Left); // already on the first digit
+        assert_eq!(entry.digit(), 2);
+        entry.handle(NavEvent::Up);
+        assert_eq!(entry.value(), 350); // clamped
+        assert_eq!(entry.to_f64(), 35.0);
+
+        assert_eq!(
+            entry.handle(NavEvent::Select),
+            Some(Outcome::Confirmed(350))
+        );
+        assert_eq!(entry.handle(NavEvent::Back), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn render_decimal_with_unit() {
+        let mut entry = NumberEntry::new(47, 0, 1000)
+            .decimals(1)
+            .unit(&[special_char::MU, b'F']);
+        entry.handle(NavEvent::Left);
+
+        assert_eq!(entry.width(), 7);
+        assert_eq!(
+            render(&entry),
+            b"\x1b[Lx0y0;  4.7\xe4F\x1b[Lx2y0;\x1b[LC\x1b[LB".to_vec()
+        );
+    }
+
+    #[test]
+    fn render_negative() {
+        let entry = NumberEntry::new(-5, -100, 100).unit(&[special_char::OMEGA]);
+
+        assert_eq!(
+            render(&entry),
+            b"\x1b[Lx0y0;  -5\xf4\x1b[Lx3y0;\x1b[LC\x1b[LB".to_vec()
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Parse the byte stream understood by the charlcd driver.
+//!
+//! The [`Parser`] follows the same rules as `charlcd_write_char` in the
+//! kernel driver, so that what it reports is what the display would do:
+//!
+//! - control characters (`\x08`, `\x0c`, `\n`, `\r` and `\t`) act
+//!   immediately;
+//! - escape sequences (`\x1b[2J`, `\x1b[H` and `\x1b[L` followed by a code)
+//!   are buffered until complete, a new line aborts the pending sequence;
+//! - unknown escape sequences are silently swallowed by the driver once they
+//!   reach [`ESCAPE_LEN`] bytes, they are reported as [`Command::Invalid`];
+//! - every other byte is displayed as a character.
+//!
+//! # Example
+//!
+//! ```
+//! use charlcd::protocol::{Command, Parser};
+//!
+//! let mut parser = Parser::new();
+//! let commands = parser.parse(b"\x1b[Lx1y0;A\x1b[LB");
+//!
+//! assert_eq!(
+//!     commands,
+//!     vec![
+//!         Command::GotoXY(Some(1), Some(0)),
+//!         Command::Char(b'A'),
+//!         Command::BlinkOn,
+//!     ]
+//! );
+//! ```
+
+use std::io::{Result, Write};
+
+use crate::Screen;
+
+/// Maximum length of an escape sequence, after the escape character.
+pub const ESCAPE_LEN: usize = 24;
+
+const ESCAPE_CHAR: u8 = 0x1b;
+
+/// An instruction of the charlcd driver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Display a character at the cursor position, and move it right.
+    Char(u8),
+    /// Move the cursor left and erase the character there (`\x08`).
+    Back,
+    /// Clear the display and move the cursor home (`\x0c` or `\x1b[2J`).
+    Clear,
+    /// Blank the rest of the line and move the cursor to the beginning of the
+    /// next line (`\n`).
+    NewLine,
+    /// Move the cursor to the beginning of the line (`\r`).
+    CarriageReturn,
+    /// Move the cursor home (`\x1b[H`).
+    Home,
+    DisplayOn,
+    DisplayOff,
+    CursorOn,
+    CursorOff,
+    BlinkOn,
+    BlinkOff,
+    BacklightOn,
+    BacklightOff,
+    FlashBacklight,
+    SmallFont,
+    LargeFont,
+    OneLine,
+    TwoLines,
+    ShiftCursorLeft,
+    ShiftCursorRight,
+    ShiftDisplayLeft,
+    ShiftDisplayRight,
+    KillEndOfLine,
+    Reinitialize,
+    /// Move the cursor, keeping the current position on missing axes.
+    GotoXY(Option<u32>, Option<u32>),
+    /// Define the custom character of a slot.
+    Generator(u8, [u8; 8]),
+    /// An escape sequence ignored by the driver, escape character included.
+    Invalid(Vec<u8>),
+}
+
+impl Command {
+    /// Write the command to `screen`, in its canonical form.
+    ///
+    /// Invalid escape sequences and gotos without any axis are not written,
+    /// as the driver would ignore them anyway, so that re-encoding parsed
+    /// commands only sends valid sequences.
+    pub fn write_to<T: Write>(&self, screen: &mut Screen<T>) -> Result<()> {
+        match *self {
+            Command::Char(c) => screen.write_all(&[c]),
+            Command::Back => screen.back(),
+            Command::Clear => screen.clear(),
+            Command::NewLine => screen.write_all(b"\n"),
+            Command::CarriageReturn => screen.write_all(b"\r"),
+            Command::Home => screen.write_all(b"\x1b[H"),
+            Command::DisplayOn => screen.display_on(),
+            Command::DisplayOff => screen.display_off(),
+            Command::CursorOn => screen.cursor_on(),
+            Command::CursorOff => screen.cursor_off(),
+            Command::BlinkOn => screen.blink_on(),
+            Command::BlinkOff => screen.blink_off(),
+            Command::BacklightOn => screen.backlight_on(),
+            Command::BacklightOff => screen.backlight_off(),
+            Command::FlashBacklight => screen.flash_backlight(),
+            Command::SmallFont => screen.small_font(),
+            Command::LargeFont => screen.large_font(),
+            Command::OneLine => screen.one_line(),
+            Command::TwoLines => screen.two_lines(),
+            Command::ShiftCursorLeft => screen.shift_cursor_left(),
+            Command::ShiftCursorRight => screen.shift_cursor_right(),
+            Command::ShiftDisplayLeft => screen.shift_display_left(),
+            Command::ShiftDisplayRight => screen.shift_display_right(),
+            Command::KillEndOfLine => screen.kill_eol(),
+            Command::Reinitialize => screen.reinit(),
+            Command::GotoXY(Some(x), Some(y)) => screen.gotoxy(x, y),
+            Command::GotoXY(Some(x), None) => screen.gotox(x),
+            Command::GotoXY(None, Some(y)) => screen.gotoy(y),
+            Command::Generator(slot, glyph) => screen.custom_char(slot, glyph),
+            Command::GotoXY(None, None) | Command::Invalid(_) => Ok(()),
+        }
+    }
+}
+
+/// Incremental parser of the byte stream sent to the driver.
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    // bytes of the pending escape sequence, after the escape character
+    escape: Option<Vec<u8>>,
+}
+
+impl Parser {
+    /// Create a new parser, outside of any escape sequence.
+    pub fn new() -> Parser {
+        Parser { escape: None }
+    }
+
+    /// Whether an escape sequence has been started but not completed yet.
+    pub fn is_pending(&self) -> bool {
+        self.escape.is_some()
+    }
+
+    /// Parse all the `bytes`, returning the completed commands.
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<Command> {
+        bytes.iter().filter_map(|&b| self.push(b)).collect()
+    }
+
+    /// Parse a single byte, returning the command it completes, if any.
+    pub fn push(&mut self, byte: u8) -> Option<Command> {
+        let seq = match &mut self.escape {
+            Some(_) if byte == b'\n' => {
+                self.escape = None;
+                return Some(Command::NewLine);
+            }
+            Some(seq) => seq,
+            None => {
+                return match byte {
+                    ESCAPE_CHAR => {
+                        self.escape = Some(Vec::new());
+                        None
+                    }
+                    b'\x08' => Some(Command::Back),
+                    b'\x0c' => Some(Command::Clear),
+                    b'\n' => Some(Command::NewLine),
+                    b'\r' => Some(Command::CarriageReturn),
+                    b'\t' => Some(Command::Char(b' ')),
+                    c => Some(Command::Char(c)),
+                };
+            }
+        };
+
+        seq.push(byte);
+        let command = match seq.as_slice() {
+            b"[2J" => Some(Command::Clear),
+            b"[H" => Some(Command::Home),
+            [b'[', b'L', code @ ..] if !code.is_empty() => special_code(code),
+            _ => None,
+        };
+
+        if command.is_some() {
+            self.escape = None;
+            command
+        } else if seq.len() >= ESCAPE_LEN {
+            let mut invalid = vec![ESCAPE_CHAR];
+            invalid.append(seq);
+            self.escape = None;
+            Some(Command::Invalid(invalid))
+        } else {
+            None
+        }
+    }
+}
+
+/// Decode the code following `\x1b[L`, `None` if it is not complete.
+fn special_code(code: &[u8]) -> Option<Command> {
+    let command = match code[0] {
+        b'D' => Command::DisplayOn,
+        b'd' => Command::DisplayOff,
+        b'C' => Command::CursorOn,
+        b'c' => Command::CursorOff,
+        b'B' => Command::BlinkOn,
+        b'b' => Command::BlinkOff,
+        b'+' => Command::BacklightOn,
+        b'-' => Command::BacklightOff,
+        b'*' => Command::FlashBacklight,
+        b'f' => Command::SmallFont,
+        b'F' => Command::LargeFont,
+        b'n' => Command::OneLine,
+        b'N' => Command::TwoLines,
+        b'l' => Command::ShiftCursorLeft,
+        b'r' => Command::ShiftCursorRight,
+        b'L' => Command::ShiftDisplayLeft,
+        b'R' => Command::ShiftDisplayRight,
+        b'k' => Command::KillEndOfLine,
+        b'I' => Command::Reinitialize,
+        b'x' | b'y' if code.contains(&b';') => parse_xy(code),
+        b'G' if code.contains(&b';') => parse_generator(code),
+        // other codes are swallowed once the sequence is too long
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn invalid_special_code(code: &[u8]) -> Command {
+    let mut invalid = b"\x1b[L".to_vec();
+    invalid.extend_from_slice(code);
+    Command::Invalid(invalid)
+}
+
+/// Parse `x<n>y<n>;`, where both axes are optional.
+fn parse_xy(code: &[u8]) -> Command {
+    let (mut x, mut y) = (None, None);
+    let mut rest = code;
+    loop {
+        let (axis, tail) = match rest {
+            [b';', ..] => return Command::GotoXY(x, y),
+            [axis @ (b'x' | b'y'), tail @ ..] => (axis, tail),
+            _ => return invalid_special_code(code),
+        };
+        let len = tail.iter().take_while(|b| b.is_ascii_digit()).count();
+        let value = std::str::from_utf8(&tail[..len])
+            .ok()
+            .and_then(|digits| digits.parse().ok());
+        if value.is_none() {
+            return invalid_special_code(code);
+        }
+        match *axis {
+            b'x' => x = value,
+            _ => y = value,
+        }
+        rest = &tail[len..];
+    }
+}
+
+/// Parse `G<slot><16 hex digits>;`.
+///
+/// Like the driver, invalid hex digits are skipped but still count for the
+/// position in the byte, and missing bytes are left blank.
+fn parse_generator(code: &[u8]) -> Command {
+    let slot = match code.get(1) {
+        Some(c @ b'0'..=b'7') => c - b'0',
+        _ => return invalid_special_code(code),
+    };
+
+    let mut glyph = [0u8; 8];
+    let mut offset = 0;
+    let mut high = true;
+    let mut value = 0u8;
+    for c in code[2..].iter().take_while(|&&c| c != b';') {
+        if offset == glyph.len() {
+            break;
+        }
+        let shift = if high { 4 } else { 0 };
+        high = !high;
+        if let Some(half) = (*c as char).to_digit(16) {
+            value |= (half as u8) << shift;
+        }
+        if shift == 0 {
+            glyph[offset] = value;
+            offset += 1;
+            value = 0;
+        }
+    }
+    Command::Generator(slot, glyph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{custom_char, Screen};
+
+    #[test]
+    fn control_characters() {
+        assert_eq!(
+            Parser::new().parse(b"a\x08\x0c\n\r\t\x1b[2J\x1b[H"),
+            vec![
+                Command::Char(b'a'),
+                Command::Back,
+                Command::Clear,
+                Command::NewLine,
+                Command::CarriageReturn,
+                Command::Char(b' '),
+                Command::Clear,
+                Command::Home,
+            ]
+        );
+    }
+
+    #[test]
+    fn screen_output() {
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        screen.gotoxy(12, 3).unwrap();
+        screen.gotox(2).unwrap();
+        screen.kill_eol().unwrap();
+        screen.custom_char(5, custom_char::RIGHT_TRIANGLE).unwrap();
+
+        assert_eq!(
+            Parser::new().parse(&buf),
+            vec![
+                Command::GotoXY(Some(12), Some(3)),
+                Command::GotoXY(Some(2), None),
+                Command::KillEndOfLine,
+                Command::Generator(5, custom_char::RIGHT_TRIANGLE),
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_sequence() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.parse(b"\x1b[Lx1"), vec![]);
+        assert!(parser.is_pending());
+        assert_eq!(parser.parse(b";"), vec![Command::GotoXY(Some(1), None)]);
+        assert!(!parser.is_pending());
+
+        // a new line aborts the sequence, like in the driver
+        assert_eq!(
+            parser.parse(b"\x1b[L\nab"),
+            vec![Command::NewLine, Command::Char(b'a'), Command::Char(b'b')]
+        );
+        assert!(!parser.is_pending());
+    }
+
+    #[test]
+    fn invalid_sequences() {
+        let mut parser = Parser::new();
+
+        assert_eq!(
+            parser.parse(b"\x1b[Lxz;"),
+            vec![Command::Invalid(b"\x1b[Lxz;".to_vec())]
+        );
+        assert_eq!(
+            parser.parse(b"\x1b[LG9;"),
+            vec![Command::Invalid(b"\x1b[LG9;".to_vec())]
+        );
+
+        // unknown codes swallow the following bytes, like the driver
+        let commands = parser.parse(b"\x1b[LZ01234567890123456789012AB");
+        assert_eq!(
+            commands,
+            vec![
+                Command::Invalid(b"\x1b[LZ012345678901234567890".to_vec()),
+                Command::Char(b'1'),
+                Command::Char(b'2'),
+                Command::Char(b'A'),
+                Command::Char(b'B'),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_canonical() {
+        let input = b"a\t\x1b[2J\x1b[Lx3;\x1b[LG9;\x1b[LG1000a1f1f0e040000;\x1b[LB";
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+        for command in Parser::new().parse(input) {
+            command.write_to(&mut screen).unwrap();
+        }
+        assert_eq!(
+            buf,
+            b"a \x0c\x1b[Lx3;\x1b[LG1000a1f1f0e040000;\x1b[LB".to_vec()
+        );
+    }
+}
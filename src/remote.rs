@@ -0,0 +1,250 @@
+//! Drive a display from another host.
+//!
+//! A [`Bridge`] listens for a TCP connection and forwards the byte stream it
+//! receives to a local screen, so that a controller on another host can drive
+//! the display with [`Screen::connect`] and the usual methods.
+//!
+//! The stream goes through a [`Parser`] and only the commands it recognizes
+//! are written again to the screen, in their canonical form: invalid escape
+//! sequences are dropped, and a sequence left incomplete by a disconnected
+//! controller never reaches the driver.
+//!
+//! A single controller is served at a time, so that the streams of two
+//! controllers cannot be spliced: the connections made while one is
+//! connected are closed at once. So that a controller that went away without
+//! closing its connection, or that stays silent, does not hold the display
+//! forever, it is disconnected when it sends nothing for
+//! [`DEFAULT_IDLE_TIMEOUT`], see [`Bridge::idle_timeout`].
+//!
+//! # Example
+//!
+//! On the controller side:
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::connect("display.local:13667")?;
+//!
+//!     screen.clear()?;
+//!     screen.write(b"Hello from afar")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! On the display side, the `charlcd-bridge` binary runs a bridge to
+//! `/dev/lcd`.
+
+use std::io::{BufWriter, ErrorKind, Read, Result, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::protocol::Parser;
+use crate::Screen;
+
+/// TCP port a bridge listens on by default.
+pub const DEFAULT_PORT: u16 = 13667;
+
+/// Time after which a silent controller is disconnected by default.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The connected controller.
+struct Controller {
+    stream: TcpStream,
+    parser: Parser,
+    // when the controller last sent something
+    active: Instant,
+}
+
+/// A bridge forwarding a validated byte stream from a TCP connection to a
+/// screen.
+///
+/// Nothing blocks: [`poll`](Bridge::poll) accepts a controller and forwards
+/// what it sent, and has to be called regularly.
+pub struct Bridge<T> {
+    listener: TcpListener,
+    screen: Screen<T>,
+    controller: Option<Controller>,
+    idle_timeout: Duration,
+}
+
+impl<T> Bridge<T>
+where
+    T: Write,
+{
+    /// Listen on `addr` for a controller of `screen`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, screen: Screen<T>) -> Result<Bridge<T>> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Bridge {
+            listener,
+            screen,
+            controller: None,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        })
+    }
+
+    /// Disconnect the controller when it sends nothing for `timeout`,
+    /// instead of [`DEFAULT_IDLE_TIMEOUT`].
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Address the bridge listens on, e.g. to get the port picked by the
+    /// system when binding port `0`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Whether a controller is connected.
+    pub fn is_connected(&self) -> bool {
+        self.controller.is_some()
+    }
+
+    /// Get a mutable reference to the screen, e.g. to show a message while
+    /// no controller is connected.
+    pub fn screen_mut(&mut self) -> &mut Screen<T> {
+        &mut self.screen
+    }
+
+    /// Unwrap this bridge, returning the screen.
+    pub fn into_inner(self) -> Screen<T> {
+        self.screen
+    }
+
+    /// Accept a controller if none is connected, then forward the commands
+    /// it sent to the screen, flushing it. The controller is disconnected if
+    /// it has been silent for longer than the idle timeout.
+    ///
+    /// Returns the number of commands forwarded. Errors of the connection
+    /// only close it, only errors of the listening socket and of the screen
+    /// are returned.
+    pub fn poll(&mut self) -> Result<usize> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) if self.controller.is_some() => {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.controller = Some(Controller {
+                        stream,
+                        parser: Parser::new(),
+                        active: Instant::now(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let controller = match &mut self.controller {
+            Some(controller) => controller,
+            None => return Ok(0),
+        };
+        let mut forwarded = 0;
+        let mut buf = [0u8; 512];
+        let connected = loop {
+            match controller.stream.read(&mut buf) {
+                Ok(0) => break false,
+                Ok(count) => {
+                    controller.active = Instant::now();
+                    for command in controller.parser.parse(&buf[..count]) {
+                        command.write_to(&mut self.screen)?;
+                        forwarded += 1;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    break controller.active.elapsed() < self.idle_timeout
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break false,
+            }
+        };
+        if !connected {
+            self.controller = None;
+        }
+
+        if forwarded > 0 {
+            self.screen.flush()?;
+        }
+        Ok(forwarded)
+    }
+}
+
+impl Screen<BufWriter<TcpStream>> {
+    /// Create a Screen instance driving the display of the [`Bridge`]
+    /// listening on `addr`.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Screen<BufWriter<TcpStream>>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Screen::new(BufWriter::new(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn poll_until<F>(bridge: &mut Bridge<Vec<u8>>, mut done: F)
+    where
+        F: FnMut(&mut Bridge<Vec<u8>>) -> bool,
+    {
+        for _ in 0..500 {
+            bridge.poll().unwrap();
+            if done(bridge) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("bridge did not reach the expected state");
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let screen = Screen::new(Vec::new());
+        let mut bridge = Bridge::bind("127.0.0.1:0", screen)
+            .unwrap()
+            .idle_timeout(Duration::from_millis(20));
+        let addr = bridge.local_addr().unwrap();
+
+        let mut silent = TcpStream::connect(addr).unwrap();
+        poll_until(&mut bridge, |b| b.is_connected());
+        poll_until(&mut bridge, |b| !b.is_connected());
+        let mut buf = [0u8; 1];
+        assert_eq!(silent.read(&mut buf).unwrap(), 0);
+
+        // the display is free for another controller
+        let _other = TcpStream::connect(addr).unwrap();
+        poll_until(&mut bridge, |b| b.is_connected());
+    }
+
+    #[test]
+    fn forward_valid_commands() {
+        let mut bridge = Bridge::bind("127.0.0.1:0", Screen::new(Vec::new())).unwrap();
+        let addr = bridge.local_addr().unwrap();
+
+        let mut screen = Screen::connect(addr).unwrap();
+        screen.write_all(b"\x1b[Lx1y0;A\x1b[LG9;B\x1b[L").unwrap();
+        screen.flush().unwrap();
+        poll_until(&mut bridge, |b| b.screen_mut().get_ref().len() == 10);
+        assert_eq!(bridge.screen_mut().get_ref(), b"\x1b[Lx1y0;AB");
+
+        // only one controller at a time
+        let mut other = TcpStream::connect(addr).unwrap();
+        poll_until(&mut bridge, |_| true);
+        let mut buf = [0u8; 1];
+        assert_eq!(other.read(&mut buf).unwrap(), 0);
+
+        // the incomplete sequence is dropped with the connection
+        drop(screen);
+        poll_until(&mut bridge, |b| !b.is_connected());
+        assert_eq!(bridge.screen_mut().get_ref(), b"\x1b[Lx1y0;AB");
+    }
+}
@@ -0,0 +1,299 @@
+//! Drive animated components from a single render loop.
+//!
+//! Blinking icons, marquees, spinners and clocks all need to be redrawn at
+//! their own pace. A [`RenderLoop`] owns the screen and a set of
+//! [`Component`]s, each of them drawing into a shared [`Frame`] and telling
+//! when it wants to be drawn again.
+//!
+//! At each tick, the loop redraws the frame if a component deadline is over
+//! or a component was invalidated, then sends only the characters that
+//! changed since the last tick, with a single flush. Ticks are also limited
+//! to a maximum rate, so that a busy component cannot saturate the bus of
+//! the display.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::{Duration, Instant, SystemTime};
+//! use charlcd::Screen;
+//! use charlcd::frame::Frame;
+//! use charlcd::render_loop::RenderLoop;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut render_loop = RenderLoop::new(screen, width, height);
+//!
+//!     // a clock redrawn every second
+//!     render_loop.add(|frame: &mut Frame, now: Instant| {
+//!         let secs = SystemTime::now()
+//!             .duration_since(SystemTime::UNIX_EPOCH)
+//!             .unwrap()
+//!             .as_secs();
+//!         let clock = format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60);
+//!         frame.print(0, 0, clock);
+//!         Some(now + Duration::from_secs(1))
+//!     });
+//!
+//!     render_loop.run(|_| true)
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::frame::Frame;
+use crate::Screen;
+
+/// Default minimum delay between two ticks that send something.
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Maximum delay between two ticks of [`RenderLoop::run`], so that its
+/// callback is called regularly even if no component has a deadline.
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Something drawn by a [`RenderLoop`].
+pub trait Component {
+    /// Draw the component into `frame` at `now`, returning when it should be
+    /// drawn again, or `None` to wait for an explicit invalidation.
+    ///
+    /// The frame is blank when the first component is drawn, components
+    /// added later are drawn over the previous ones.
+    fn draw(&mut self, frame: &mut Frame, now: Instant) -> Option<Instant>;
+}
+
+impl<F> Component for F
+where
+    F: FnMut(&mut Frame, Instant) -> Option<Instant>,
+{
+    fn draw(&mut self, frame: &mut Frame, now: Instant) -> Option<Instant> {
+        self(frame, now)
+    }
+}
+
+/// Identifier of a component added to a [`RenderLoop`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComponentId(usize);
+
+struct Entry {
+    component: Box<dyn Component>,
+    deadline: Option<Instant>,
+    dirty: bool,
+}
+
+/// A render loop owning a screen.
+pub struct RenderLoop<T> {
+    screen: Screen<T>,
+    entries: Vec<Entry>,
+    frame: Frame,
+    // what was last sent to the screen, None before the first tick
+    displayed: Option<Frame>,
+    min_interval: Duration,
+    last_tick: Option<Instant>,
+}
+
+impl<T> RenderLoop<T>
+where
+    T: Write,
+{
+    /// Create a new render loop drawing on the whole `width` x `height`
+    /// `screen`.
+    pub fn new(screen: Screen<T>, width: u32, height: u32) -> RenderLoop<T> {
+        RenderLoop {
+            screen,
+            entries: Vec::new(),
+            frame: Frame::new(width, height),
+            displayed: None,
+            min_interval: DEFAULT_MIN_INTERVAL,
+            last_tick: None,
+        }
+    }
+
+    /// Send at most `rate` updates per second to the screen, instead of 20.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    pub fn max_rate(mut self, rate: u32) -> RenderLoop<T> {
+        assert!(rate > 0, "rate must be positive");
+        self.min_interval = Duration::from_secs(1) / rate;
+        self
+    }
+
+    /// Add a component, drawn at the next tick.
+    pub fn add<C: Component + 'static>(&mut self, component: C) -> ComponentId {
+        self.entries.push(Entry {
+            component: Box::new(component),
+            deadline: None,
+            dirty: true,
+        });
+        ComponentId(self.entries.len() - 1)
+    }
+
+    /// Redraw at the next tick, e.g. because the data shown by the component
+    /// `id` changed.
+    pub fn invalidate(&mut self, id: ComponentId) {
+        if let Some(entry) = self.entries.get_mut(id.0) {
+            entry.dirty = true;
+        }
+    }
+
+    /// Redraw the whole screen at the next tick, e.g. after it was changed
+    /// outside of the render loop.
+    pub fn invalidate_all(&mut self) {
+        self.displayed = None;
+        for entry in &mut self.entries {
+            entry.dirty = true;
+        }
+    }
+
+    /// Get a mutable reference to the screen, e.g. to load custom
+    /// characters.
+    pub fn screen_mut(&mut self) -> &mut Screen<T> {
+        &mut self.screen
+    }
+
+    /// Unwrap this render loop, returning the screen.
+    pub fn into_inner(self) -> Screen<T> {
+        self.screen
+    }
+
+    /// When the next tick has something to do, `None` if it is waiting for an
+    /// invalidation.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let earliest = self.last_tick.map(|last| last + self.min_interval);
+        if self.displayed.is_none() || self.entries.iter().any(|e| e.dirty) {
+            return Some(earliest.unwrap_or_else(Instant::now));
+        }
+        let next = self.entries.iter().filter_map(|e| e.deadline).min()?;
+        Some(earliest.map_or(next, |earliest| next.max(earliest)))
+    }
+
+    /// Redraw the frame and send what changed to the screen if a component
+    /// needs it at `now` and the rate limit allows it.
+    ///
+    /// Returns whether the screen was updated.
+    pub fn tick(&mut self, now: Instant) -> Result<bool> {
+        if let Some(last) = self.last_tick {
+            if now < last + self.min_interval {
+                return Ok(false);
+            }
+        }
+        let due = self.displayed.is_none()
+            || self
+                .entries
+                .iter()
+                .any(|e| e.dirty || e.deadline.is_some_and(|d| d <= now));
+        if !due {
+            return Ok(false);
+        }
+
+        // components may overlap, so all of them are drawn again
+        self.frame.clear();
+        for entry in &mut self.entries {
+            entry.deadline = entry.component.draw(&mut self.frame, now);
+            entry.dirty = false;
+        }
+
+        match &self.displayed {
+            Some(displayed) => {
+                self.frame.render_diff(displayed, &mut self.screen, 0, 0)?;
+            }
+            None => self.frame.render(&mut self.screen, 0, 0)?,
+        }
+        self.screen.flush()?;
+        self.displayed = Some(self.frame.clone());
+        self.last_tick = Some(now);
+        Ok(true)
+    }
+
+    /// Tick until `keep_going` returns `false`, sleeping until the next
+    /// deadline in between.
+    ///
+    /// `keep_going` is called before each tick, at least every 100ms, and can
+    /// be used to invalidate components or to access the screen.
+    pub fn run<F>(&mut self, mut keep_going: F) -> Result<()>
+    where
+        F: FnMut(&mut RenderLoop<T>) -> bool,
+    {
+        while keep_going(self) {
+            let now = Instant::now();
+            self.tick(now)?;
+
+            let wait = match self.next_deadline() {
+                Some(deadline) => deadline.saturating_duration_since(now),
+                None => IDLE_INTERVAL,
+            };
+            thread::sleep(wait.min(IDLE_INTERVAL));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn written(render_loop: &mut RenderLoop<Vec<u8>>) -> Vec<u8> {
+        std::mem::take(render_loop.screen_mut().get_mut())
+    }
+
+    #[test]
+    fn redraw_on_deadline() {
+        let start = Instant::now();
+        let mut render_loop = RenderLoop::new(Screen::new(Vec::new()), 4, 1);
+        let mut count = 0u8;
+        render_loop.add(move |frame: &mut Frame, now: Instant| {
+            frame.set(0, 0, b'0' + count);
+            count += 1;
+            Some(now + Duration::from_secs(1))
+        });
+
+        assert!(render_loop.tick(start).unwrap());
+        assert_eq!(written(&mut render_loop), b"\x1b[Lx0y0;0   ");
+        assert_eq!(
+            render_loop.next_deadline(),
+            Some(start + Duration::from_secs(1))
+        );
+
+        assert!(!render_loop
+            .tick(start + Duration::from_millis(500))
+            .unwrap());
+        assert!(render_loop.tick(start + Duration::from_secs(1)).unwrap());
+        assert_eq!(written(&mut render_loop), b"\x1b[Lx0y0;1");
+    }
+
+    #[test]
+    fn invalidate_and_rate_limit() {
+        let start = Instant::now();
+        let mut render_loop = RenderLoop::new(Screen::new(Vec::new()), 4, 1).max_rate(10);
+        let value = Rc::new(Cell::new(b'a'));
+        let shown = value.clone();
+        let id = render_loop.add(move |frame: &mut Frame, _| {
+            frame.set(3, 0, shown.get());
+            None
+        });
+
+        render_loop.tick(start).unwrap();
+        written(&mut render_loop);
+        assert_eq!(render_loop.next_deadline(), None);
+        assert!(!render_loop.tick(start + Duration::from_secs(1)).unwrap());
+
+        // changes are coalesced until the rate limit allows a new update
+        value.set(b'b');
+        render_loop.invalidate(id);
+        value.set(b'c');
+        assert_eq!(
+            render_loop.next_deadline(),
+            Some(start + Duration::from_millis(100))
+        );
+        assert!(!render_loop.tick(start + Duration::from_millis(50)).unwrap());
+        assert!(render_loop
+            .tick(start + Duration::from_millis(100))
+            .unwrap());
+        assert_eq!(written(&mut render_loop), b"\x1b[Lx3y0;c");
+    }
+}
@@ -0,0 +1,421 @@
+//! Keep track of what is displayed on the screen.
+//!
+//! The charlcd driver is write-only: there is no way to read back the
+//! characters displayed or the state of the cursor. A [`Shadow`] writer sits
+//! between a [`Screen`] and the driver, and parses everything that goes
+//! through it with the [`protocol`][crate::protocol] parser to maintain a
+//! [`Snapshot`] of the display: characters, cursor position, display flags
+//! and custom characters.
+//!
+//! A snapshot can be taken at any time and restored later, which allows to
+//! temporarily take over the display (e.g. for a dialog) and put back what
+//! was there before.
+//!
+//! The shadow assumes a blank display with the driver default flags when it
+//! is created, so the screen should be cleared or reinitialized first.
+//! Display shifts, fonts and number of lines are not tracked.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let screen = Screen::default()?;
+//!     let (width, height) = (screen.width()?, screen.height()?);
+//!     let mut screen = screen.shadowed(width, height);
+//!
+//!     screen.reinit()?;
+//!     screen.write(b"hello, world!")?;
+//!     let saved = screen.get_ref().snapshot().clone();
+//!
+//!     screen.clear()?;
+//!     screen.write(b"something else")?;
+//!
+//!     saved.restore(&mut screen)?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Result, Write};
+
+use crate::marquee::DDRAM_LINE_WIDTH;
+use crate::protocol::{Command, Parser};
+use crate::Screen;
+
+/// Number of custom characters slots.
+const CGRAM_SLOTS: usize = 8;
+
+/// Display flags set with the escape codes of the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flags {
+    pub display: bool,
+    pub cursor: bool,
+    pub blink: bool,
+    pub backlight: bool,
+}
+
+impl Default for Flags {
+    /// Flags set by the driver when the display is initialized.
+    fn default() -> Self {
+        Flags {
+            display: true,
+            cursor: true,
+            blink: true,
+            backlight: true,
+        }
+    }
+}
+
+impl Flags {
+    /// Write the escape codes switching `screen` from the `previous` flags
+    /// to these ones, all of them if the previous flags are unknown.
+    ///
+    /// Returns whether anything was written.
+    pub fn write_diff<T: Write>(
+        &self,
+        previous: Option<Flags>,
+        screen: &mut Screen<T>,
+    ) -> Result<bool> {
+        let changed = |f: fn(&Flags) -> bool| previous.map(|p| f(&p)) != Some(f(self));
+
+        let mut written = false;
+        if changed(|f| f.display) {
+            if self.display {
+                screen.display_on()?;
+            } else {
+                screen.display_off()?;
+            }
+            written = true;
+        }
+        if changed(|f| f.cursor) {
+            if self.cursor {
+                screen.cursor_on()?;
+            } else {
+                screen.cursor_off()?;
+            }
+            written = true;
+        }
+        if changed(|f| f.blink) {
+            if self.blink {
+                screen.blink_on()?;
+            } else {
+                screen.blink_off()?;
+            }
+            written = true;
+        }
+        if changed(|f| f.backlight) {
+            if self.backlight {
+                screen.backlight_on()?;
+            } else {
+                screen.backlight_off()?;
+            }
+            written = true;
+        }
+        Ok(written)
+    }
+}
+
+/// The state of a display at a given time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    width: u32,
+    height: u32,
+    cells: Vec<u8>,
+    x: u32,
+    y: u32,
+    flags: Flags,
+    glyphs: [Option<[u8; 8]>; CGRAM_SLOTS],
+}
+
+impl Snapshot {
+    /// Create the snapshot of a blank `width` x `height` display, with the
+    /// cursor home and the driver default flags.
+    pub fn new(width: u32, height: u32) -> Snapshot {
+        Snapshot {
+            width,
+            height,
+            cells: vec![b' '; (width * height) as usize],
+            x: 0,
+            y: 0,
+            flags: Flags::default(),
+            glyphs: [None; CGRAM_SLOTS],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Character displayed at the (`x`, `y`) position, if it is on the
+    /// display.
+    pub fn cell(&self, x: u32, y: u32) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.cells[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Characters displayed on line `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not a line of the display.
+    pub fn line(&self, y: u32) -> &[u8] {
+        assert!(y < self.height, "line out of the display");
+        let start = (y * self.width) as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    /// Position of the cursor, which may be out of the display.
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Custom character defined in `slot`, if it was defined since the
+    /// shadow was created.
+    pub fn glyph(&self, slot: u8) -> Option<[u8; 8]> {
+        self.glyphs.get(slot as usize).copied().flatten()
+    }
+
+    /// Update the snapshot as the driver would update the display.
+    pub fn apply(&mut self, command: &Command) {
+        match *command {
+            Command::Char(c) => {
+                self.set(self.x, self.y, c);
+                if self.x < DDRAM_LINE_WIDTH {
+                    self.x += 1;
+                }
+            }
+            Command::Back => {
+                self.x = self.x.saturating_sub(1);
+                self.set(self.x, self.y, b' ');
+            }
+            Command::Clear => {
+                self.cells.fill(b' ');
+                self.x = 0;
+                self.y = 0;
+            }
+            Command::NewLine => {
+                self.blank_eol();
+                self.x = 0;
+                self.y = (self.y + 1) % self.height.max(1);
+            }
+            Command::CarriageReturn => self.x = 0,
+            Command::Home => {
+                self.x = 0;
+                self.y = 0;
+            }
+            Command::DisplayOn => self.flags.display = true,
+            Command::DisplayOff => self.flags.display = false,
+            Command::CursorOn => self.flags.cursor = true,
+            Command::CursorOff => self.flags.cursor = false,
+            Command::BlinkOn => self.flags.blink = true,
+            Command::BlinkOff => self.flags.blink = false,
+            Command::BacklightOn => self.flags.backlight = true,
+            Command::BacklightOff => self.flags.backlight = false,
+            Command::ShiftCursorLeft => self.x = self.x.saturating_sub(1),
+            Command::ShiftCursorRight => {
+                if self.x < DDRAM_LINE_WIDTH {
+                    self.x += 1;
+                }
+            }
+            Command::KillEndOfLine => self.blank_eol(),
+            Command::Reinitialize => {
+                *self = Snapshot {
+                    glyphs: self.glyphs,
+                    ..Snapshot::new(self.width, self.height)
+                }
+            }
+            Command::GotoXY(x, y) => {
+                self.x = x.unwrap_or(self.x);
+                self.y = y.unwrap_or(self.y);
+            }
+            Command::Generator(slot, glyph) => self.glyphs[slot as usize] = Some(glyph),
+            Command::FlashBacklight
+            | Command::SmallFont
+            | Command::LargeFont
+            | Command::OneLine
+            | Command::TwoLines
+            | Command::ShiftDisplayLeft
+            | Command::ShiftDisplayRight
+            | Command::Invalid(_) => {}
+        }
+    }
+
+    /// Put the display of `screen` back in the state of this snapshot:
+    /// custom characters, characters, flags and cursor position.
+    pub fn restore<T: Write>(&self, screen: &mut Screen<T>) -> Result<()> {
+        for (slot, glyph) in self.glyphs.iter().enumerate() {
+            if let Some(glyph) = glyph {
+                screen.custom_char(slot as u8, *glyph)?;
+            }
+        }
+        for y in 0..self.height {
+            screen.gotoxy(0, y)?;
+            screen.write_all(self.line(y))?;
+        }
+
+        self.flags.write_diff(None, screen)?;
+
+        screen.gotoxy(self.x, self.y)
+    }
+
+    fn set(&mut self, x: u32, y: u32, c: u8) {
+        if x < self.width && y < self.height {
+            self.cells[(y * self.width + x) as usize] = c;
+        }
+    }
+
+    fn blank_eol(&mut self) {
+        for x in self.x..self.width {
+            self.set(x, self.y, b' ');
+        }
+    }
+}
+
+/// A writer keeping a [`Snapshot`] of what the bytes written to it display.
+pub struct Shadow<W> {
+    writer: W,
+    parser: Parser,
+    snapshot: Snapshot,
+}
+
+impl<W> Shadow<W>
+where
+    W: Write,
+{
+    /// Create a new [`Shadow`] of a blank `width` x `height` display, that
+    /// will forward the written bytes to `writer`.
+    pub fn new(writer: W, width: u32, height: u32) -> Shadow<W> {
+        Shadow {
+            writer,
+            parser: Parser::new(),
+            snapshot: Snapshot::new(width, height),
+        }
+    }
+
+    /// Current state of the display.
+    ///
+    /// The bytes buffered by the underlying writer are taken into account,
+    /// even if they have not been flushed yet.
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying writer. Bytes written
+    /// directly to it are not tracked.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> Write for Shadow<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let count = self.writer.write(buf)?;
+        for &b in &buf[..count] {
+            if let Some(command) = self.parser.push(b) {
+                self.snapshot.apply(&command);
+            }
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<T> Screen<T>
+where
+    T: Write,
+{
+    /// Keep track of what is displayed on this `width` x `height` screen,
+    /// see [`Shadow`].
+    pub fn shadowed(self, width: u32, height: u32) -> Screen<Shadow<T>> {
+        Screen::new(Shadow::new(self.into_inner(), width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_char;
+
+    fn screen(width: u32, height: u32) -> Screen<Shadow<Vec<u8>>> {
+        Screen::new(Vec::new()).shadowed(width, height)
+    }
+
+    #[test]
+    fn track_characters() {
+        let mut screen = screen(8, 2);
+        screen.write_all(b"hello\nworld").unwrap();
+        screen.back().unwrap();
+        screen.gotoxy(1, 0).unwrap();
+        screen.kill_eol().unwrap();
+        screen.write_all(b"\ta").unwrap();
+
+        let snapshot = screen.get_ref().snapshot();
+        assert_eq!(snapshot.line(0), b"h a     ");
+        assert_eq!(snapshot.line(1), b"worl    ");
+        assert_eq!(snapshot.cursor(), (3, 0));
+    }
+
+    #[test]
+    fn track_flags_and_glyphs() {
+        let mut screen = screen(8, 2);
+        screen.cursor_off().unwrap();
+        screen.backlight_off().unwrap();
+        screen.custom_char(2, custom_char::UP_TRIANGLE).unwrap();
+
+        let snapshot = screen.get_ref().snapshot();
+        assert!(!snapshot.flags().cursor);
+        assert!(!snapshot.flags().backlight);
+        assert!(snapshot.flags().blink);
+        assert_eq!(snapshot.glyph(2), Some(custom_char::UP_TRIANGLE));
+        assert_eq!(snapshot.glyph(3), None);
+    }
+
+    #[test]
+    fn restore() {
+        let mut screen = screen(4, 2);
+        screen.write_all(b"ab").unwrap();
+        screen.blink_off().unwrap();
+        let saved = screen.get_ref().snapshot().clone();
+
+        screen.clear().unwrap();
+        screen.write_all(b"xyz").unwrap();
+        screen.blink_on().unwrap();
+        screen.get_mut().get_mut().clear();
+
+        saved.restore(&mut screen).unwrap();
+        assert_eq!(screen.get_ref().snapshot(), &saved);
+        assert_eq!(
+            screen.get_ref().get_ref(),
+            b"\x1b[Lx0y0;ab  \x1b[Lx0y1;    \x1b[LD\x1b[LC\x1b[Lb\x1b[L+\x1b[Lx2y0;"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Combine several displays into a larger one.
+//!
+//! A [`Span`] places physical displays, its modules, side by side or on top
+//! of each other in a larger logical display, e.g. two 20x4 modules as a 40x4
+//! or a 20x8 display. It is a writer that keeps a [`Snapshot`] of the logical
+//! display, so that a [`Screen`] draws on it as on a single display.
+//!
+//! When flushed, the span writes to each module the part of the logical
+//! display it shows, splitting text runs at module boundaries and only
+//! writing what changed since the previous flush. Custom characters are
+//! loaded on every module, so that a slot shows the same glyph wherever it is
+//! displayed. The display and backlight flags apply to every module, the
+//! cursor and blink flags only to the module showing the cursor.
+//!
+//! Like the DDRAM line of a single display, logical lines are at most 40
+//! characters long, and there are at most [`MAX_LINES`] of them. Display
+//! shifts, fonts and number of lines are ignored.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::fs::OpenOptions;
+//! use std::io::{BufWriter, Write};
+//! use charlcd::Screen;
+//! use charlcd::span::Span;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let open = |path| -> std::io::Result<_> {
+//!         let file = OpenOptions::new().write(true).open(path)?;
+//!         Ok(Screen::new(BufWriter::new(file)))
+//!     };
+//!
+//!     // two 20x4 modules making a 40x4 display
+//!     let span = Span::new()
+//!         .module(open("/dev/lcd")?, 0, 0, 20, 4)
+//!         .module(open("/dev/lcd1")?, 20, 0, 20, 4);
+//!     let mut screen = Screen::new(span);
+//!
+//!     screen.clear()?;
+//!     screen.gotoxy(14, 1)?;
+//!     screen.write(b"across both modules")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Result, Write};
+use std::mem;
+
+use crate::frame::Frame;
+use crate::marquee::DDRAM_LINE_WIDTH;
+use crate::protocol::{Command, Parser};
+use crate::shadow::{Flags, Snapshot};
+use crate::Screen;
+
+/// Number of custom character slots.
+const SLOTS: u8 = 8;
+
+/// Maximum number of lines of the logical display.
+pub const MAX_LINES: u32 = 256;
+
+/// A physical display of a [`Span`].
+struct Module<T>
+where
+    T: Write,
+{
+    screen: Screen<T>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // state of the display, `None` until first rendered
+    displayed: Option<Frame>,
+    loaded: [Option<[u8; 8]>; SLOTS as usize],
+    flags: Option<Flags>,
+    cursor: Option<(u32, u32)>,
+}
+
+impl<T> Module<T>
+where
+    T: Write,
+{
+    /// Position of the logical (`x`, `y`) position on this module, if it
+    /// shows it.
+    fn local(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let (x, y) = (x.checked_sub(self.x)?, y.checked_sub(self.y)?);
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// Bring the module up to date with the logical `snapshot`, flashing its
+    /// backlight if `flash` is set, then flush it.
+    fn render(&mut self, snapshot: &Snapshot, flash: bool) -> Result<()> {
+        let mut frame = Frame::new(self.width, self.height);
+        for row in 0..self.height.min(snapshot.height().saturating_sub(self.y)) {
+            let line = snapshot.line(self.y + row);
+            let start = (self.x as usize).min(line.len());
+            frame.print(0, row, &line[start..]);
+        }
+
+        let mut written = false;
+        for slot in 0..SLOTS {
+            if let Some(glyph) = snapshot.glyph(slot) {
+                if self.loaded[slot as usize] != Some(glyph) {
+                    self.screen.custom_char(slot, glyph)?;
+                    self.loaded[slot as usize] = Some(glyph);
+                    written = true;
+                }
+            }
+        }
+
+        match &self.displayed {
+            Some(displayed) => written |= frame.render_diff(displayed, &mut self.screen, 0, 0)? > 0,
+            None => {
+                frame.render(&mut self.screen, 0, 0)?;
+                written = true;
+            }
+        }
+        self.displayed = Some(frame);
+
+        let (x, y) = snapshot.cursor();
+        let cursor = self.local(x, y);
+        let mut flags = snapshot.flags();
+        if cursor.is_none() {
+            flags.cursor = false;
+            flags.blink = false;
+        }
+        written |= flags.write_diff(self.flags.replace(flags), &mut self.screen)?;
+        if flash {
+            self.screen.flash_backlight()?;
+        }
+        // writing moved the cursor of the display
+        if let Some((x, y)) = cursor {
+            if (flags.cursor || flags.blink) && (written || self.cursor != cursor) {
+                self.screen.gotoxy(x, y)?;
+            }
+        }
+        self.cursor = cursor;
+
+        self.screen.flush()
+    }
+}
+
+/// A logical display made of several physical displays.
+pub struct Span<T>
+where
+    T: Write,
+{
+    modules: Vec<Module<T>>,
+    parser: Parser,
+    snapshot: Snapshot,
+    flash: bool,
+}
+
+impl<T> Default for Span<T>
+where
+    T: Write,
+{
+    fn default() -> Self {
+        Span::new()
+    }
+}
+
+impl<T> Span<T>
+where
+    T: Write,
+{
+    /// Create a new [`Span`] without modules.
+    pub fn new() -> Span<T> {
+        Span {
+            modules: Vec::new(),
+            parser: Parser::new(),
+            snapshot: Snapshot::new(0, 0),
+            flash: false,
+        }
+    }
+
+    /// Add `screen`, a `width` x `height` display, as a module showing the
+    /// logical display from the (`x`, `y`) position.
+    ///
+    /// The logical display grows to cover every module, and is blank.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module extends past the 40th column or the
+    /// [`MAX_LINES`]th line of the logical display.
+    pub fn module(mut self, screen: Screen<T>, x: u32, y: u32, width: u32, height: u32) -> Self {
+        assert!(
+            x.checked_add(width)
+                .is_some_and(|end| end <= DDRAM_LINE_WIDTH),
+            "modules must fit in the first {} columns",
+            DDRAM_LINE_WIDTH
+        );
+        assert!(
+            y.checked_add(height).is_some_and(|end| end <= MAX_LINES),
+            "modules must fit in the first {} lines",
+            MAX_LINES
+        );
+        self.modules.push(Module {
+            screen,
+            x,
+            y,
+            width,
+            height,
+            displayed: None,
+            loaded: [None; SLOTS as usize],
+            flags: None,
+            cursor: None,
+        });
+        let width = self.modules.iter().map(|m| m.x + m.width).max();
+        let height = self.modules.iter().map(|m| m.y + m.height).max();
+        self.snapshot = Snapshot::new(width.unwrap_or(0), height.unwrap_or(0));
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.snapshot.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.snapshot.height()
+    }
+
+    /// Current state of the logical display, flushed or not.
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Number of modules.
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Get a reference to the screen of the module `index`, in the order
+    /// modules were added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no module `index`.
+    pub fn get_ref(&self, index: usize) -> &Screen<T> {
+        &self.modules[index].screen
+    }
+
+    /// Unwrap this span, returning the screens of the modules.
+    pub fn into_inner(self) -> Vec<Screen<T>> {
+        self.modules.into_iter().map(|m| m.screen).collect()
+    }
+}
+
+impl<T> Write for Span<T>
+where
+    T: Write,
+{
+    /// Draw on the logical display, nothing is written to the modules until
+    /// the span is flushed.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for &b in buf {
+            match self.parser.push(b) {
+                Some(Command::FlashBacklight) => self.flash = true,
+                Some(command) => self.snapshot.apply(&command),
+                None => {}
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let flash = mem::take(&mut self.flash);
+        for module in &mut self.modules {
+            module.render(&self.snapshot, flash)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_char;
+
+    fn output(span: &mut Span<Vec<u8>>) -> Vec<Vec<u8>> {
+        span.modules
+            .iter_mut()
+            .map(|m| mem::take(m.screen.get_mut()))
+            .collect()
+    }
+
+    #[test]
+    fn side_by_side() {
+        let span = Span::new()
+            .module(Screen::new(Vec::new()), 0, 0, 4, 1)
+            .module(Screen::new(Vec::new()), 4, 0, 4, 1);
+        let mut screen = Screen::new(span);
+        assert_eq!(
+            (screen.get_ref().width(), screen.get_ref().height()),
+            (8, 1)
+        );
+
+        screen.cursor_off().unwrap();
+        screen.blink_off().unwrap();
+        screen.write_all(b"abcdefg").unwrap();
+        screen.flush().unwrap();
+        let flags = b"\x1b[LD\x1b[Lc\x1b[Lb\x1b[L+".to_vec();
+        assert_eq!(
+            output(screen.get_mut()),
+            [
+                [b"\x1b[Lx0y0;abcd".to_vec(), flags.clone()].concat(),
+                [b"\x1b[Lx0y0;efg ".to_vec(), flags].concat(),
+            ]
+        );
+
+        // only changes are written, to the module showing them
+        screen.gotoxy(5, 0).unwrap();
+        screen.write_all(b"X").unwrap();
+        screen.flush().unwrap();
+        assert_eq!(
+            output(screen.get_mut()),
+            [b"".to_vec(), b"\x1b[Lx1y0;X".to_vec()]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "modules must fit in the first 40 columns")]
+    fn reject_modules_past_ddram() {
+        Span::new()
+            .module(Screen::new(Vec::new()), 0, 0, 20, 4)
+            .module(Screen::new(Vec::new()), 24, 0, 20, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "modules must fit in the first 256 lines")]
+    fn reject_modules_past_max_lines() {
+        Span::new().module(Screen::new(Vec::new()), 0, 0, 40, u32::MAX - 1);
+    }
+
+    #[test]
+    fn stacked_with_cursor_and_glyphs() {
+        let span = Span::new()
+            .module(Screen::new(Vec::new()), 0, 0, 2, 1)
+            .module(Screen::new(Vec::new()), 0, 1, 2, 1);
+        let mut screen = Screen::new(span);
+        screen.flush().unwrap();
+        output(screen.get_mut());
+
+        screen.custom_char(1, custom_char::UP_TRIANGLE).unwrap();
+        screen.write_all(b"\n\x01").unwrap();
+        screen.flush().unwrap();
+        // glyphs are loaded everywhere, the cursor moves to the second module
+        let glyph = b"\x1b[LG10000040e1f000000;".to_vec();
+        assert_eq!(
+            output(screen.get_mut()),
+            [
+                [glyph.clone(), b"\x1b[Lc\x1b[Lb".to_vec()].concat(),
+                [glyph, b"\x1b[Lx0y0;\x01\x1b[LC\x1b[LB\x1b[Lx1y0;".to_vec()].concat(),
+            ]
+        );
+    }
+}
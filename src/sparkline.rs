@@ -0,0 +1,357 @@
+//! Plot the last samples of a metric as a one-row sparkline.
+//!
+//! Each character cell of the sparkline shows 5 samples, one per pixel
+//! column, as small bars auto-scaled between the minimum and the maximum of
+//! the kept samples. The glyphs needed for the current samples are computed
+//! on each draw and allocated dynamically in a range of custom characters
+//! slots, reusing the slots that already hold the right glyph.
+//!
+//! When there are more distinct glyphs than available slots, the vertical
+//! resolution is halved (8, 4, 2 then 1 levels) until the glyphs fit, so that
+//! the sparkline degrades gracefully instead of displaying garbage.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::sparkline::Sparkline;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut spark = Sparkline::new(10).labels(4, 0);
+//!
+//!     for temp in &[21.0, 22.5, 24.0, 23.0, 25.5, 27.0, 26.0] {
+//!         spark.push(*temp);
+//!     }
+//!
+//!     screen.clear()?;
+//!     spark.draw(&mut screen, 0, 0)?; // "  21", graph, "  27"
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::special_char;
+use crate::Screen;
+
+/// Number of samples displayed in a character cell.
+pub const SAMPLES_PER_CELL: usize = 5;
+
+const FULL_GLYPH: [u8; 8] = [0b11111; 8];
+
+/// A one-row sparkline of the last samples of a metric.
+pub struct Sparkline {
+    width: u32,
+    samples: VecDeque<f32>,
+    first_slot: u8,
+    slot_count: u8,
+    labels: Option<(usize, usize)>,
+    uploaded: Vec<Option<[u8; 8]>>,
+    resolution: u8,
+}
+
+impl Sparkline {
+    /// Create a new empty sparkline `width` characters wide, using all the
+    /// custom characters slots (`0` to `7`).
+    pub fn new(width: u32) -> Sparkline {
+        Sparkline {
+            width,
+            samples: VecDeque::with_capacity(width as usize * SAMPLES_PER_CELL),
+            first_slot: 0,
+            slot_count: 8,
+            labels: None,
+            uploaded: vec![None; 8],
+            resolution: 8,
+        }
+    }
+
+    /// Only use the `count` custom characters slots starting from `first`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0 or if the slots go past the 8th one.
+    pub fn slots(mut self, first: u8, count: u8) -> Sparkline {
+        assert!(count > 0, "a sparkline needs at least one slot");
+        assert!(
+            first as u32 + count as u32 <= 8,
+            "custom character slots go up to 7"
+        );
+        self.first_slot = first;
+        self.slot_count = count;
+        self.uploaded = vec![None; count as usize];
+        self
+    }
+
+    /// Print the minimum value before the graph and the maximum value after
+    /// it, right aligned on `width` characters with `precision` decimals.
+    ///
+    /// Decimals are dropped from a value that does not fit, and a value that
+    /// does not fit without decimals is shown as `#` signs.
+    pub fn labels(mut self, width: usize, precision: usize) -> Sparkline {
+        self.labels = Some((width, precision));
+        self
+    }
+
+    /// Maximum number of samples kept, that is the number of pixel columns of
+    /// the graph.
+    pub fn capacity(&self) -> usize {
+        self.width as usize * SAMPLES_PER_CELL
+    }
+
+    /// Add a new sample, dropping the oldest one if the sparkline is full.
+    pub fn push(&mut self, value: f32) {
+        self.samples.push_back(value);
+        while self.samples.len() > self.capacity() {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Samples currently kept, from the oldest to the newest.
+    pub fn samples(&self) -> impl Iterator<Item = &f32> {
+        self.samples.iter()
+    }
+
+    /// Number of vertical levels used by the last draw (8 at best).
+    pub fn resolution(&self) -> u8 {
+        self.resolution
+    }
+
+    /// Draw the sparkline (and its labels) starting at the (`x`, `y`)
+    /// position.
+    ///
+    /// The newest samples are on the right side. The custom characters that
+    /// are needed and not already in the screen memory are uploaded first.
+    pub fn draw<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+    ) -> std::io::Result<()> {
+        let (min, max) = self.bounds();
+
+        let (resolution, cells) = [8, 4, 2, 1]
+            .iter()
+            .map(|&resolution| (resolution, self.cells(resolution, min, max)))
+            .find(|(_, cells)| distinct_glyphs(cells).len() <= self.slot_count as usize)
+            .expect("a single level needs at most one custom character");
+        self.resolution = resolution;
+
+        let mut line = Vec::with_capacity(self.width as usize + 8);
+        if let Some((width, precision)) = self.labels {
+            line.extend(format_label(min, width, precision));
+        }
+
+        for glyph in &cells {
+            let code = match *glyph {
+                [0, 0, 0, 0, 0, 0, 0, 0] => b' ',
+                FULL_GLYPH => special_char::BLOCK,
+                glyph => self.allocate(screen, glyph, &cells)?,
+            };
+            line.push(code);
+        }
+
+        if let Some((width, precision)) = self.labels {
+            line.extend(format_label(max, width, precision));
+        }
+
+        screen.gotoxy(x, y)?;
+        screen.write_all(&line)
+    }
+
+    /// Minimum and maximum of the kept samples.
+    fn bounds(&self) -> (f32, f32) {
+        let min = self.samples.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self
+            .samples
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        if min > max {
+            (0.0, 0.0)
+        } else {
+            (min, max)
+        }
+    }
+
+    /// Glyphs of every cell of the graph, for a given vertical resolution.
+    fn cells(&self, resolution: u8, min: f32, max: f32) -> Vec<[u8; 8]> {
+        let capacity = self.capacity();
+        let step = 8 / resolution;
+
+        // pixel height of each column, right aligned, 0 meaning no sample
+        let mut heights = vec![0u8; capacity];
+        let offset = capacity - self.samples.len();
+        for (i, value) in self.samples.iter().enumerate() {
+            let ratio = if max > min {
+                (value - min) / (max - min)
+            } else {
+                1.0
+            };
+            let level = (ratio * (resolution - 1) as f32).round() as u8;
+            heights[offset + i] = (level + 1) * step;
+        }
+
+        heights
+            .chunks(SAMPLES_PER_CELL)
+            .map(|columns| {
+                let mut glyph = [0u8; 8];
+                for (col, height) in columns.iter().enumerate() {
+                    let bit = 1 << (SAMPLES_PER_CELL - 1 - col);
+                    for row in glyph.iter_mut().skip(8 - *height as usize) {
+                        *row |= bit;
+                    }
+                }
+                glyph
+            })
+            .collect()
+    }
+
+    /// Code of a slot holding `glyph`, uploading it if needed.
+    ///
+    /// Slots holding glyphs that are not part of `cells` are reused first.
+    fn allocate<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        glyph: [u8; 8],
+        cells: &[[u8; 8]],
+    ) -> std::io::Result<u8> {
+        if let Some(slot) = self.uploaded.iter().position(|g| *g == Some(glyph)) {
+            return Ok(self.first_slot + slot as u8);
+        }
+
+        let slot = self
+            .uploaded
+            .iter()
+            .position(|g| match g {
+                Some(g) => !cells.contains(g),
+                None => true,
+            })
+            .expect("not enough custom character slots");
+        screen.custom_char(self.first_slot + slot as u8, glyph)?;
+        self.uploaded[slot] = Some(glyph);
+        Ok(self.first_slot + slot as u8)
+    }
+}
+
+/// Glyphs that need a custom character slot.
+fn distinct_glyphs(cells: &[[u8; 8]]) -> Vec<[u8; 8]> {
+    let mut glyphs: Vec<[u8; 8]> = Vec::new();
+    for glyph in cells {
+        if *glyph != [0; 8] && *glyph != FULL_GLYPH && !glyphs.contains(glyph) {
+            glyphs.push(*glyph);
+        }
+    }
+    glyphs
+}
+
+fn format_label(value: f32, width: usize, precision: usize) -> Vec<u8> {
+    for precision in (0..=precision).rev() {
+        let label = format!(
+            "{:>width$.precision$}",
+            value,
+            width = width,
+            precision = precision
+        );
+        if label.len() <= width {
+            return label.into_bytes();
+        }
+    }
+    vec![b'#'; width]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_last_samples() {
+        let mut spark = Sparkline::new(1);
+        for i in 0..7 {
+            spark.push(i as f32);
+        }
+        let samples: Vec<f32> = spark.samples().copied().collect();
+        assert_eq!(samples, vec![2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn draw_with_labels() {
+        let mut spark = Sparkline::new(2).labels(3, 0);
+        for value in &[0.0, 7.0, 7.0, 7.0, 7.0, 7.0] {
+            spark.push(*value);
+        }
+
+        let mut buf = Vec::new();
+        spark.draw(&mut Screen::new(&mut buf), 0, 1).unwrap();
+
+        // empty columns then a 1 pixel bar, then a full cell
+        let mut expected = b"\x1b[LG00000000000000001;\x1b[Lx0y1;  0".to_vec();
+        expected.extend(b"\x00\xff  7");
+        assert_eq!(buf, expected);
+        assert_eq!(spark.resolution(), 8);
+    }
+
+    #[test]
+    fn reuses_uploaded_slots() {
+        let mut spark = Sparkline::new(1);
+        spark.push(0.0);
+        spark.push(1.0);
+
+        let mut buf = Vec::new();
+        spark.draw(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        assert!(buf.starts_with(b"\x1b[LG0"));
+
+        let mut buf = Vec::new();
+        spark.draw(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        assert_eq!(buf, b"\x1b[Lx0y0;\x00");
+    }
+
+    #[test]
+    fn label_overflow() {
+        assert_eq!(format_label(1234.0, 5, 2), b" 1234");
+        assert_eq!(format_label(-1.5, 4, 2), b"-1.5");
+        assert_eq!(format_label(1234.5, 3, 1), b"###");
+    }
+
+    #[test]
+    fn degrades_resolution() {
+        let mut spark = Sparkline::new(3).slots(7, 1);
+        for value in &[0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0] {
+            spark.push(*value);
+        }
+        for _ in 0..5 {
+            spark.push(7.0);
+        }
+
+        // 1 and 2 pixel bars need two slots, at half the resolution both
+        // are 2 pixel bars
+        let mut buf = Vec::new();
+        spark.draw(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        assert_eq!(spark.resolution(), 4);
+        assert_eq!(
+            buf,
+            b"\x1b[LG70000000000001f1f;\x1b[Lx0y0;\x07\x07\xff".to_vec()
+        );
+    }
+
+    #[test]
+    fn zero_width() {
+        let mut spark = Sparkline::new(0).labels(1, 0);
+        spark.push(1.0);
+        assert_eq!(spark.samples().count(), 0);
+
+        let mut buf = Vec::new();
+        spark.draw(&mut Screen::new(&mut buf), 0, 0).unwrap();
+        assert_eq!(buf, b"\x1b[Lx0y0;00");
+    }
+
+    #[test]
+    #[should_panic(expected = "custom character slots go up to 7")]
+    fn slots_past_the_last_one() {
+        Sparkline::new(1).slots(250, 10);
+    }
+}
@@ -37,5 +37,7 @@ pub const MEAN: u8 = 0xf8;
 
 /// ·
 pub const MEDIAN_DOT: u8 = 0xa5;
+/// °
+pub const DEGREE: u8 = 0xdf;
 /// ▮
 pub const BLOCK: u8 = 0xff;
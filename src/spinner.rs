@@ -0,0 +1,308 @@
+//! Activity indicators animated in a single custom character.
+//!
+//! A [`Spinner`] owns one custom character slot and animates it by uploading
+//! a new bitmap into that slot at each step. The character only has to be
+//! written once on the display: every occurrence of it animates at once,
+//! without rewriting the display memory.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::thread;
+//! use std::time::{Duration, Instant};
+//! use charlcd::Screen;
+//! use charlcd::spinner::{Spinner, SpinnerKind};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut spinner = Spinner::new(SpinnerKind::Bar).slot(7);
+//!
+//!     screen.clear()?;
+//!     screen.write(b"Updating ")?;
+//!     screen.write(&[spinner.char()])?;
+//!
+//!     let start = Instant::now();
+//!     while start.elapsed() < Duration::from_secs(10) {
+//!         if spinner.update(&mut screen, start.elapsed())? {
+//!             screen.flush()?;
+//!         }
+//!         thread::sleep(Duration::from_millis(20));
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Write;
+use std::time::Duration;
+
+use crate::custom_char::mirror_x;
+use crate::Screen;
+
+/// |
+#[rustfmt::skip]
+const BAR_VERTICAL: [u8; 8] = [
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00100,
+    0b00000,
+];
+
+/// /
+#[rustfmt::skip]
+const BAR_SLASH: [u8; 8] = [
+    0b00001,
+    0b00001,
+    0b00010,
+    0b00100,
+    0b01000,
+    0b10000,
+    0b10000,
+    0b00000,
+];
+
+/// -
+#[rustfmt::skip]
+const BAR_HORIZONTAL: [u8; 8] = [
+    0b00000,
+    0b00000,
+    0b00000,
+    0b11111,
+    0b00000,
+    0b00000,
+    0b00000,
+    0b00000,
+];
+
+/// ⧗, full at the top
+#[rustfmt::skip]
+const HOURGLASS_TOP: [u8; 8] = [
+    0b11111,
+    0b11111,
+    0b01110,
+    0b00100,
+    0b01010,
+    0b10001,
+    0b11111,
+    0b00000,
+];
+
+/// ⧗, half full
+#[rustfmt::skip]
+const HOURGLASS_HALF: [u8; 8] = [
+    0b11111,
+    0b10001,
+    0b01110,
+    0b00100,
+    0b01010,
+    0b11111,
+    0b11111,
+    0b00000,
+];
+
+/// ⧗, full at the bottom
+#[rustfmt::skip]
+const HOURGLASS_BOTTOM: [u8; 8] = [
+    0b11111,
+    0b10001,
+    0b01010,
+    0b00100,
+    0b01110,
+    0b11111,
+    0b11111,
+    0b00000,
+];
+
+/// ♥
+#[rustfmt::skip]
+const HEART_BIG: [u8; 8] = [
+    0b00000,
+    0b01010,
+    0b11111,
+    0b11111,
+    0b01110,
+    0b00100,
+    0b00000,
+    0b00000,
+];
+
+/// ♥, contracted
+#[rustfmt::skip]
+const HEART_SMALL: [u8; 8] = [
+    0b00000,
+    0b00000,
+    0b01010,
+    0b01110,
+    0b00100,
+    0b00000,
+    0b00000,
+    0b00000,
+];
+
+/// Predefined animations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpinnerKind {
+    /// A rotating bar: `|`, `/`, `-`, `\`.
+    Bar,
+    /// A dot bouncing from one side of the character to the other.
+    BouncingDot,
+    /// An hourglass whose sand falls to the bottom.
+    Hourglass,
+    /// A pulsing heart.
+    Heart,
+}
+
+impl SpinnerKind {
+    /// Bitmaps of the animation, in order.
+    pub fn frames(self) -> Vec<[u8; 8]> {
+        match self {
+            SpinnerKind::Bar => vec![BAR_VERTICAL, BAR_SLASH, BAR_HORIZONTAL, mirror_x(BAR_SLASH)],
+            SpinnerKind::BouncingDot => [0, 1, 2, 3, 4, 3, 2, 1]
+                .iter()
+                .map(|&column| {
+                    let dot = 0b10000 >> column;
+                    [0, 0, 0, dot, dot, 0, 0, 0]
+                })
+                .collect(),
+            SpinnerKind::Hourglass => vec![HOURGLASS_TOP, HOURGLASS_HALF, HOURGLASS_BOTTOM],
+            SpinnerKind::Heart => vec![HEART_BIG, HEART_SMALL],
+        }
+    }
+
+    /// Default delay between two frames of the animation.
+    pub fn interval(self) -> Duration {
+        match self {
+            SpinnerKind::Bar => Duration::from_millis(150),
+            SpinnerKind::BouncingDot => Duration::from_millis(100),
+            SpinnerKind::Hourglass => Duration::from_millis(400),
+            SpinnerKind::Heart => Duration::from_millis(500),
+        }
+    }
+}
+
+/// An animated custom character.
+pub struct Spinner {
+    frames: Vec<[u8; 8]>,
+    interval: Duration,
+    slot: u8,
+    last_frame: Option<usize>,
+}
+
+impl Spinner {
+    /// Create a new spinner playing a predefined animation in the custom
+    /// character slot `0`.
+    pub fn new(kind: SpinnerKind) -> Spinner {
+        Spinner::custom(kind.frames(), kind.interval())
+    }
+
+    /// Create a new spinner playing `frames` in loop, each of them shown for
+    /// `interval`, in the custom character slot `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames` is empty.
+    pub fn custom(frames: Vec<[u8; 8]>, interval: Duration) -> Spinner {
+        assert!(!frames.is_empty(), "a spinner needs at least one frame");
+        Spinner {
+            frames,
+            interval,
+            slot: 0,
+            last_frame: None,
+        }
+    }
+
+    /// Animate the custom character `slot` instead of `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is greater than `7`.
+    pub fn slot(mut self, slot: u8) -> Spinner {
+        assert!(slot < 8, "custom character slots go from 0 to 7");
+        self.slot = slot;
+        self
+    }
+
+    /// Change the delay between two frames.
+    pub fn interval(mut self, interval: Duration) -> Spinner {
+        self.interval = interval;
+        self
+    }
+
+    /// Character to write on the display to show the spinner.
+    pub fn char(&self) -> u8 {
+        self.slot
+    }
+
+    /// Index of the frame shown once `elapsed` time passed since the
+    /// animation started.
+    pub fn frame_at(&self, elapsed: Duration) -> usize {
+        if self.interval.is_zero() {
+            return 0;
+        }
+        (elapsed.as_nanos() / self.interval.as_nanos()) as usize % self.frames.len()
+    }
+
+    /// Upload the frame shown once `elapsed` time passed since the animation
+    /// started.
+    ///
+    /// Returns `true` if something was written to the screen, that is if the
+    /// frame changed since the last update.
+    pub fn update<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        elapsed: Duration,
+    ) -> std::io::Result<bool> {
+        let frame = self.frame_at(elapsed);
+        if self.last_frame == Some(frame) {
+            return Ok(false);
+        }
+
+        screen.custom_char(self.slot, self.frames[frame])?;
+        self.last_frame = Some(frame);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let spinner = Spinner::new(SpinnerKind::Bar);
+        assert_eq!(spinner.frame_at(Duration::from_millis(0)), 0);
+        assert_eq!(spinner.frame_at(Duration::from_millis(449)), 2);
+        assert_eq!(spinner.frame_at(Duration::from_millis(600)), 0);
+
+        let dots = SpinnerKind::BouncingDot.frames();
+        assert_eq!(dots.len(), 8);
+        assert_eq!(dots[4][3], 0b00001);
+        assert_eq!(dots[7][4], 0b01000);
+    }
+
+    #[test]
+    fn upload_on_change() {
+        let mut spinner = Spinner::new(SpinnerKind::Heart).slot(3);
+        let mut buf = Vec::new();
+        let mut screen = Screen::new(&mut buf);
+
+        assert!(spinner.update(&mut screen, Duration::ZERO).unwrap());
+        assert!(!spinner
+            .update(&mut screen, Duration::from_millis(499))
+            .unwrap());
+        assert!(spinner
+            .update(&mut screen, Duration::from_millis(500))
+            .unwrap());
+
+        assert_eq!(spinner.char(), 3);
+        assert_eq!(
+            buf,
+            b"\x1b[LG3000a1f1f0e040000;\x1b[LG300000a0e04000000;".to_vec()
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Mirror a screen onto several writers.
+//!
+//! A [`Tee`] writes everything written to it to each of its targets, so that
+//! a single [`Screen`][crate::Screen] drives several displays showing the same
+//! content, or also feeds an emulator or a log file.
+//!
+//! Each target has a [`Policy`] deciding what happens when writing to it
+//! fails: the whole tee fails, the target is dropped, or the write is
+//! retried. Writes succeed once the bytes were written to every target still
+//! active, and the errors to return are reported by the next flush instead:
+//! a failed write is never repeated, so the healthy targets keep showing the
+//! same content.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::fs::{File, OpenOptions};
+//! use std::io::Write;
+//! use charlcd::Screen;
+//! use charlcd::tee::{Policy, Tee};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let open = |path| OpenOptions::new().write(true).open(path);
+//!     let tee = Tee::new()
+//!         .target(open("/dev/lcd")?, Policy::Fail)
+//!         .target(open("/dev/lcd1")?, Policy::Retry(3))
+//!         .target(File::create("/tmp/lcd.log")?, Policy::Drop);
+//!     let mut screen = Screen::new(tee);
+//!
+//!     screen.clear()?;
+//!     screen.write(b"Welcome!")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// What to do when writing to a target of a [`Tee`] fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Stop writing to every target: the error is returned by the next
+    /// flush, then by every later write and flush.
+    Fail,
+    /// Stop writing to the target and carry on with the others.
+    Drop,
+    /// Try again up to the given number of times, resuming where the failed
+    /// write stopped, then return the error from the next flush.
+    Retry(u32),
+}
+
+/// A target of a [`Tee`].
+struct Target {
+    writer: Box<dyn Write + Send>,
+    policy: Policy,
+    // error that stopped writing to the target
+    error: Option<Error>,
+}
+
+impl Target {
+    /// Apply the policy of the target to the result of `operation`, which is
+    /// called again as long as it has to be retried.
+    fn run<F>(&mut self, mut operation: F) -> Result<()>
+    where
+        F: FnMut(&mut dyn Write) -> Result<()>,
+    {
+        let mut retries = match self.policy {
+            Policy::Retry(retries) => retries,
+            _ => 0,
+        };
+        loop {
+            match operation(&mut *self.writer) {
+                Ok(()) => return Ok(()),
+                Err(_) if retries > 0 => retries -= 1,
+                Err(e) if self.policy == Policy::Drop => {
+                    self.error = Some(e);
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// A writer duplicating what is written to it to several targets.
+#[derive(Default)]
+pub struct Tee {
+    targets: Vec<Target>,
+    // error of a write, returned by the next flush
+    pending: Option<Error>,
+}
+
+impl Tee {
+    /// Create a new [`Tee`] without targets.
+    pub fn new() -> Tee {
+        Tee::default()
+    }
+
+    /// Add `writer` to the targets, with `policy` on errors.
+    pub fn target<W>(mut self, writer: W, policy: Policy) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.targets.push(Target {
+            writer: Box::new(writer),
+            policy,
+            error: None,
+        });
+        self
+    }
+
+    /// Number of targets, dropped ones included.
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Number of targets still written to.
+    pub fn active(&self) -> usize {
+        self.targets.iter().filter(|t| t.is_active()).count()
+    }
+
+    /// Error that stopped writing to the target `index`, in the order
+    /// targets were added, `None` if it is still active.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no target `index`.
+    pub fn error(&self, index: usize) -> Option<&Error> {
+        self.targets[index].error.as_ref()
+    }
+
+    /// Run the operation made by `operation` on every active target,
+    /// returning the first error.
+    fn run<F, G>(&mut self, mut operation: F) -> Result<()>
+    where
+        F: FnMut() -> G,
+        G: FnMut(&mut dyn Write) -> Result<()>,
+    {
+        let mut result = Ok(());
+        for target in self.targets.iter_mut().filter(|t| t.is_active()) {
+            let status = target.run(operation());
+            if let (Err(e), Policy::Fail) = (&status, target.policy) {
+                target.error = Some(copy(e));
+            }
+            if result.is_ok() {
+                result = status;
+            }
+        }
+        result
+    }
+
+    /// Error of a target with the [`Policy::Fail`] policy, once one failed.
+    fn failure(&self) -> Option<Error> {
+        self.targets
+            .iter()
+            .filter(|t| t.policy == Policy::Fail)
+            .find_map(|t| t.error.as_ref())
+            .map(copy)
+    }
+}
+
+fn copy(error: &Error) -> Error {
+    Error::new(error.kind(), error.to_string())
+}
+
+impl Write for Tee {
+    /// Write the whole of `buf` to every active target.
+    ///
+    /// This only fails once a target with the [`Policy::Fail`] policy failed,
+    /// the errors of the targets are returned by the next flush.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if let Some(e) = self.failure() {
+            return Err(e);
+        }
+        let result = self.run(|| {
+            // resume where a failed write stopped when retrying
+            let mut written = 0;
+            move |writer: &mut dyn Write| {
+                while written < buf.len() {
+                    match writer.write(&buf[written..]) {
+                        Ok(0) => return Err(Error::from(ErrorKind::WriteZero)),
+                        Ok(count) => written += count,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(())
+            }
+        });
+        if let Err(e) = result {
+            self.pending.get_or_insert(e);
+        }
+        Ok(buf.len())
+    }
+
+    /// Flush every active target, returning the first error of the writes
+    /// since the previous flush, if any, or of the flush itself.
+    fn flush(&mut self) -> Result<()> {
+        if let Some(e) = self.pending.take() {
+            return Err(e);
+        }
+        if let Some(e) = self.failure() {
+            return Err(e);
+        }
+        self.run(|| |writer: &mut dyn Write| writer.flush())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Screen;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer sharing its output, failing `failures` times first.
+    #[derive(Clone, Default)]
+    struct Device {
+        output: Arc<Mutex<Vec<u8>>>,
+        failures: Arc<Mutex<u32>>,
+    }
+
+    impl Device {
+        fn failing(failures: u32) -> Device {
+            Device {
+                failures: Arc::new(Mutex::new(failures)),
+                ..Device::default()
+            }
+        }
+
+        fn output(&self) -> Vec<u8> {
+            self.output.lock().unwrap().clone()
+        }
+    }
+
+    impl Write for Device {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(Error::new(ErrorKind::BrokenPipe, "unplugged"));
+            }
+            // write at most 4 bytes at once
+            let count = buf.len().min(4);
+            self.output.lock().unwrap().extend_from_slice(&buf[..count]);
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn mirror() {
+        let (front, back) = (Device::default(), Device::default());
+        let tee = Tee::new()
+            .target(front.clone(), Policy::Fail)
+            .target(back.clone(), Policy::Fail);
+        let mut screen = Screen::new(tee);
+        screen.clear().unwrap();
+        screen.write_all(b"hello").unwrap();
+        screen.flush().unwrap();
+
+        assert_eq!(front.output(), b"\x0chello");
+        assert_eq!(back.output(), b"\x0chello");
+    }
+
+    #[test]
+    fn policies() {
+        let (drop, retry) = (Device::failing(1), Device::failing(2));
+        let mut tee = Tee::new()
+            .target(drop.clone(), Policy::Drop)
+            .target(retry.clone(), Policy::Retry(2));
+
+        assert_eq!(tee.write(b"abcdef").unwrap(), 6);
+        assert!(tee.flush().is_ok());
+        assert_eq!(drop.output(), b"");
+        assert_eq!(retry.output(), b"abcdef");
+        assert_eq!(tee.active(), 1);
+        assert!(tee.error(0).is_some());
+        assert!(tee.error(1).is_none());
+
+        // out of retries, the error is returned by the next flush only
+        *retry.failures.lock().unwrap() = 3;
+        assert_eq!(tee.write(b"gh").unwrap(), 2);
+        assert_eq!(tee.flush().unwrap_err().to_string(), "unplugged");
+        assert_eq!(tee.write(b"ij").unwrap(), 2);
+        assert!(tee.flush().is_ok());
+        assert_eq!(retry.output(), b"abcdefij");
+    }
+
+    #[test]
+    fn fail_all() {
+        let (fail, other) = (Device::failing(1), Device::default());
+        let tee = Tee::new()
+            .target(fail.clone(), Policy::Fail)
+            .target(other.clone(), Policy::Fail);
+
+        // the error is only returned by the flush, so that the write is not
+        // repeated on the other targets
+        let mut screen = Screen::new(tee);
+        screen.write_all(b"abcdef").unwrap();
+        assert_eq!(screen.flush().unwrap_err().to_string(), "unplugged");
+        assert_eq!(fail.output(), b"");
+        assert_eq!(other.output(), b"abcdef");
+
+        // then nothing is written anymore
+        assert!(screen.write_all(b"gh").is_err());
+        assert!(screen.flush().is_err());
+        assert_eq!(fail.output(), b"");
+        assert_eq!(other.output(), b"abcdef");
+        assert_eq!(screen.get_ref().active(), 1);
+        assert!(screen.get_ref().error(0).is_some());
+    }
+}
@@ -0,0 +1,416 @@
+//! Enter text with a few buttons, one character at a time.
+//!
+//! A [`TextEntry`] displays an editable line, with the hardware cursor
+//! (underline and blinking block) on the character being edited. The
+//! character under the cursor is chosen by cycling through an alphabet with
+//! up and down (or a rotary encoder), and the cursor is moved with left and
+//! right. This is enough to enter a hostname or a Wi-Fi password on a panel
+//! with four buttons.
+//!
+//! After the last character of the alphabet, the cycle goes through three
+//! pseudo-characters that act when selected:
+//!
+//! - insert: insert a character at the cursor position;
+//! - delete: delete the character at the cursor position;
+//! - done: confirm the text.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use charlcd::Screen;
+//! use charlcd::input::{InputReader, Outcome};
+//! use charlcd::text_entry::TextEntry;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?;
+//!     let mut input = InputReader::from_dev_path(Path::new("/dev/input/event0"))?;
+//!
+//!     let mut entry = TextEntry::new(screen.width()?).max_len(63).mask(b'*');
+//!     entry.load_glyphs(&mut screen)?;
+//!
+//!     let password = loop {
+//!         entry.render(&mut screen, 0, 1)?;
+//!         screen.flush()?;
+//!
+//!         if let Some(outcome) = entry.handle(input.read_event()?) {
+//!             break outcome;
+//!         }
+//!     };
+//!     entry.hide_cursor(&mut screen)?;
+//!
+//!     if let Outcome::Confirmed(password) = password {
+//!         println!("{}", password);
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Write;
+
+use crate::input::{NavEvent, Outcome};
+use crate::Screen;
+
+/// Number of custom characters slots used by a [`TextEntry`].
+pub const GLYPH_COUNT: u8 = 3;
+
+/// Characters offered by default: letters, digits and common symbols.
+pub const DEFAULT_ALPHABET: &str =
+    "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 .-_@:/!#$%&*+=?";
+
+/// Insert
+#[rustfmt::skip]
+const INSERT: [u8; 8] = [
+    0b00100,
+    0b00100,
+    0b11111,
+    0b00100,
+    0b00100,
+    0b00000,
+    0b11111,
+    0b00000,
+];
+
+/// Delete
+#[rustfmt::skip]
+const DELETE: [u8; 8] = [
+    0b00000,
+    0b00111,
+    0b01001,
+    0b10101,
+    0b01001,
+    0b00111,
+    0b00000,
+    0b00000,
+];
+
+/// ✓
+#[rustfmt::skip]
+const DONE: [u8; 8] = [
+    0b00000,
+    0b00001,
+    0b00010,
+    0b10100,
+    0b01000,
+    0b00000,
+    0b00000,
+    0b00000,
+];
+
+/// Pseudo-characters following the alphabet, in cycle order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pseudo {
+    Insert,
+    Delete,
+    Done,
+}
+
+const PSEUDOS: [Pseudo; GLYPH_COUNT as usize] = [Pseudo::Insert, Pseudo::Delete, Pseudo::Done];
+
+/// A single line text input field.
+pub struct TextEntry {
+    width: u32,
+    first_slot: u8,
+    alphabet: Vec<u8>,
+    mask: Option<u8>,
+    max_len: usize,
+    text: Vec<u8>,
+    cursor: usize,
+    // pseudo-character shown at the cursor position instead of the text
+    pseudo: Option<Pseudo>,
+    // index of the first displayed character
+    scroll: usize,
+}
+
+impl TextEntry {
+    /// Create a new empty field taking `width` characters, using the
+    /// [`DEFAULT_ALPHABET`] and the custom characters slots `0` to `2`.
+    ///
+    /// The text is scrolled horizontally if it does not fit in the field.
+    pub fn new(width: u32) -> TextEntry {
+        TextEntry {
+            width: width.max(1),
+            first_slot: 0,
+            alphabet: DEFAULT_ALPHABET.bytes().collect(),
+            mask: None,
+            max_len: usize::MAX,
+            text: Vec::new(),
+            cursor: 0,
+            pseudo: None,
+            scroll: 0,
+        }
+    }
+
+    /// Use the [`GLYPH_COUNT`] custom characters slots starting from `slot`
+    /// instead of `0`.
+    pub fn first_slot(mut self, slot: u8) -> TextEntry {
+        self.first_slot = slot;
+        self
+    }
+
+    /// Offer the characters of `alphabet`, in this order, instead of the
+    /// [`DEFAULT_ALPHABET`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alphabet` is empty or contains non-ASCII characters.
+    pub fn alphabet(mut self, alphabet: &str) -> TextEntry {
+        assert!(
+            !alphabet.is_empty() && alphabet.is_ascii(),
+            "alphabet must be non-empty ASCII"
+        );
+        self.alphabet = alphabet.bytes().collect();
+        self
+    }
+
+    /// Display every character but the edited one as `mask`, for secrets.
+    pub fn mask(mut self, mask: u8) -> TextEntry {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Limit the text to `max_len` characters.
+    pub fn max_len(mut self, max_len: usize) -> TextEntry {
+        self.max_len = max_len.max(1);
+        self.text.truncate(self.max_len);
+        self.cursor = self.cursor.min(self.last_position());
+        self
+    }
+
+    /// Start with `text` instead of an empty text, with the cursor after its
+    /// last character. Non-ASCII characters are dropped.
+    pub fn initial(mut self, text: &str) -> TextEntry {
+        self.text = text
+            .bytes()
+            .filter(u8::is_ascii)
+            .take(self.max_len)
+            .collect();
+        self.cursor = self.last_position();
+        self
+    }
+
+    /// Store the pseudo-characters into the screen memory.
+    pub fn load_glyphs<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.custom_char(self.first_slot, INSERT)?;
+        screen.custom_char(self.first_slot + 1, DELETE)?;
+        screen.custom_char(self.first_slot + 2, DONE)?;
+        Ok(())
+    }
+
+    /// The text entered so far.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.text).expect("text is ASCII")
+    }
+
+    /// Position of the cursor in the text.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Update the field according to a navigation event.
+    ///
+    /// Up and down (or a rotation) cycle the character under the cursor,
+    /// left and right move the cursor, select acts on a pseudo-character or
+    /// moves to the next character, and back cancels the entry.
+    pub fn handle(&mut self, event: NavEvent) -> Option<Outcome<String>> {
+        match event {
+            NavEvent::Up => self.cycle(1),
+            NavEvent::Down => self.cycle(-1),
+            NavEvent::Rotate(steps) => self.cycle(steps),
+            NavEvent::Left => self.move_cursor(-1),
+            NavEvent::Right => self.move_cursor(1),
+            NavEvent::Back => return Some(Outcome::Cancelled),
+            NavEvent::Select => match self.pseudo {
+                Some(Pseudo::Insert) => self.insert(),
+                Some(Pseudo::Delete) => self.delete(),
+                Some(Pseudo::Done) => return Some(self.confirm()),
+                None => self.move_cursor(1),
+            },
+        }
+        None
+    }
+
+    /// Insert the first character of the alphabet at the cursor position, if
+    /// the text is not full.
+    pub fn insert(&mut self) {
+        self.pseudo = None;
+        if self.text.len() < self.max_len {
+            self.text.insert(self.cursor, self.alphabet[0]);
+        }
+    }
+
+    /// Delete the character at the cursor position, or the last character if
+    /// the cursor is after it.
+    pub fn delete(&mut self) {
+        self.pseudo = None;
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        } else if self.text.pop().is_some() {
+            self.cursor -= 1;
+        }
+    }
+
+    /// Confirm the text entered so far.
+    pub fn confirm(&mut self) -> Outcome<String> {
+        self.pseudo = None;
+        Outcome::Confirmed(self.text().to_string())
+    }
+
+    /// Draw the field at the (`x`, `y`) position, then move the cursor to the
+    /// edited character and make it visible.
+    pub fn render<T: Write>(
+        &mut self,
+        screen: &mut Screen<T>,
+        x: u32,
+        y: u32,
+    ) -> std::io::Result<()> {
+        // keep the cursor visible
+        let width = self.width as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + width {
+            self.scroll = self.cursor + 1 - width;
+        }
+
+        let mut line: Vec<u8> = (self.scroll..self.scroll + width)
+            .map(|i| match (self.text.get(i), self.mask) {
+                (Some(_), Some(mask)) if i != self.cursor => mask,
+                (Some(&c), _) => c,
+                (None, _) => b' ',
+            })
+            .collect();
+        if let Some(pseudo) = self.pseudo {
+            line[self.cursor - self.scroll] = self.first_slot + pseudo as u8;
+        }
+
+        screen.gotoxy(x, y)?;
+        screen.write_all(&line)?;
+        screen.gotoxy(x + (self.cursor - self.scroll) as u32, y)?;
+        screen.cursor_on()?;
+        screen.blink_on()?;
+        Ok(())
+    }
+
+    /// Hide the cursor shown by [`TextEntry::render`].
+    pub fn hide_cursor<T: Write>(&self, screen: &mut Screen<T>) -> std::io::Result<()> {
+        screen.cursor_off()?;
+        screen.blink_off()?;
+        Ok(())
+    }
+
+    /// Last position of the cursor: after the last character, unless the
+    /// text is full.
+    fn last_position(&self) -> usize {
+        self.text.len().min(self.max_len - 1)
+    }
+
+    fn move_cursor(&mut self, steps: i32) {
+        self.pseudo = None;
+        let cursor = (self.cursor as i64 + steps as i64).clamp(0, self.last_position() as i64);
+        self.cursor = cursor as usize;
+    }
+
+    fn cycle(&mut self, steps: i32) {
+        let count = (self.alphabet.len() + PSEUDOS.len()) as i64;
+        let current = match self.pseudo {
+            Some(pseudo) => Some(self.alphabet.len() + pseudo as usize),
+            None => self
+                .text
+                .get(self.cursor)
+                .map(|c| self.alphabet.iter().position(|a| a == c).unwrap_or(0)),
+        };
+
+        // the position after the last character starts empty, before the
+        // first character of the alphabet
+        let next = match current {
+            Some(index) => (index as i64 + steps as i64).rem_euclid(count),
+            None if steps > 0 => (steps as i64 - 1).rem_euclid(count),
+            None => (steps as i64).rem_euclid(count),
+        } as usize;
+
+        match self.alphabet.get(next) {
+            Some(&c) => {
+                self.pseudo = None;
+                if self.cursor < self.text.len() {
+                    self.text[self.cursor] = c;
+                } else {
+                    self.text.push(c);
+                }
+            }
+            None => self.pseudo = Some(PSEUDOS[next - self.alphabet.len()]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(entry: &mut TextEntry, events: &[NavEvent]) -> Option<Outcome<String>> {
+        events.iter().find_map(|&e| entry.handle(e))
+    }
+
+    #[test]
+    fn enter_text() {
+        let mut entry = TextEntry::new(8).alphabet("abc");
+
+        let outcome = feed(
+            &mut entry,
+            &[
+                NavEvent::Up,         // a
+                NavEvent::Right,      //
+                NavEvent::Down,       // a Done
+                NavEvent::Rotate(-3), // ac
+                NavEvent::Select,     // ac
+                NavEvent::Rotate(2),  // acb
+                NavEvent::Rotate(4),  // ac Done
+                NavEvent::Select,
+            ],
+        );
+        assert_eq!(outcome, Some(Outcome::Confirmed("acb".to_string())));
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        let mut entry = TextEntry::new(8).alphabet("xyz").initial("xyz");
+        assert_eq!(entry.cursor(), 3);
+
+        entry.handle(NavEvent::Rotate(-2)); // Delete
+        entry.handle(NavEvent::Select);
+        assert_eq!(entry.text(), "xy");
+        assert_eq!(entry.cursor(), 2);
+
+        entry.handle(NavEvent::Left);
+        entry.handle(NavEvent::Left);
+        entry.handle(NavEvent::Rotate(-3)); // Insert
+        entry.handle(NavEvent::Select);
+        assert_eq!(entry.text(), "xxy");
+
+        assert_eq!(entry.handle(NavEvent::Back), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn max_len() {
+        let mut entry = TextEntry::new(8).max_len(2).initial("abc");
+        assert_eq!(entry.text(), "ab");
+        assert_eq!(entry.cursor(), 1);
+
+        entry.handle(NavEvent::Right);
+        assert_eq!(entry.cursor(), 1);
+        entry.insert();
+        assert_eq!(entry.text(), "ab");
+    }
+
+    #[test]
+    fn render_masked_and_scrolled() {
+        let mut entry = TextEntry::new(4).mask(b'*').initial("secret");
+        entry.handle(NavEvent::Rotate(-1)); // Done
+
+        let mut buf = Vec::new();
+        entry.render(&mut Screen::new(&mut buf), 2, 1).unwrap();
+
+        assert_eq!(buf, b"\x1b[Lx2y1;***\x02\x1b[Lx5y1;\x1b[LC\x1b[LB".to_vec());
+    }
+}
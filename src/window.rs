@@ -0,0 +1,411 @@
+//! Draw in a part of the display with local coordinates.
+//!
+//! A [`Window`] is a writer that confines what is written to it to a
+//! rectangle of the display, see [`Screen::window`]. Drawing on the window
+//! works as on a display of the size of the window:
+//!
+//! * positions given to [`gotoxy`](Screen::gotoxy) are relative to the
+//!   top-left corner of the window;
+//! * characters out of the window are not displayed, text is clipped instead
+//!   of running over the rest of the line;
+//! * [`clear`](Screen::clear), [`kill_eol`](Screen::kill_eol), new lines and
+//!   [`reinit`](Screen::reinit) only blank the window, where the driver would
+//!   blank the whole display or the whole line.
+//!
+//! Flags and custom characters are shared by the whole display and are
+//! written as is, display shifts are ignored.
+//!
+//! Windows are taken from a [`Shared`] screen (see [`Screen::shared`]), so
+//! that several of them can be alive at the same time: components can then
+//! be handed a window of the display rather than the whole display, each
+//! drawing in its own area without knowing where it is.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use charlcd::Screen;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut screen = Screen::default()?.shared();
+//!
+//!     // a status bar on the first line, the main area below
+//!     let mut status = screen.window(0, 0, 20, 1);
+//!     let mut main = screen.window(0, 1, 20, 3);
+//!
+//!     main.clear()?;
+//!     status.write(b"12:34")?;
+//!     main.write(b"Hello\nworld")?;
+//!     status.write(b"      Wi-Fi ok")?;
+//!     screen.flush()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::io::{Result, Write};
+use std::rc::Rc;
+
+use crate::marquee::DDRAM_LINE_WIDTH;
+use crate::protocol::{Command, Parser};
+use crate::Screen;
+
+struct Inner<T> {
+    screen: Screen<T>,
+    // position of the cursor of the display, `None` if unknown
+    cursor: Option<(u32, u32)>,
+}
+
+/// A screen shared by several windows.
+///
+/// Cloning the handle gives another handle to the same screen. Bytes written
+/// directly to the handle are written to the screen as is.
+pub struct Shared<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Shared<T>
+where
+    T: Write,
+{
+    /// Share `screen` between several windows.
+    pub fn new(screen: Screen<T>) -> Shared<T> {
+        Shared {
+            inner: Rc::new(RefCell::new(Inner {
+                screen,
+                cursor: None,
+            })),
+        }
+    }
+
+    /// Get a window of the `width` x `height` rectangle of the screen with
+    /// its top-left corner at the (`x`, `y`) position.
+    pub fn window(&self, x: u32, y: u32, width: u32, height: u32) -> Window<T> {
+        Window::new(self.clone(), x, y, width, height)
+    }
+
+    /// Borrow the shared screen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the screen is being written to.
+    pub fn screen(&self) -> Ref<'_, Screen<T>> {
+        Ref::map(self.inner.borrow(), |inner| &inner.screen)
+    }
+
+    /// Mutably borrow the shared screen. Bytes written directly to it are
+    /// not tracked by the windows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the screen is already borrowed.
+    pub fn screen_mut(&self) -> RefMut<'_, Screen<T>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.cursor = None;
+        RefMut::map(inner, |inner| &mut inner.screen)
+    }
+
+    /// Unwrap this handle, returning the screen, or the handle itself if
+    /// other handles or windows are still alive.
+    pub fn try_into_inner(self) -> std::result::Result<Screen<T>, Shared<T>> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => Ok(inner.into_inner().screen),
+            Err(inner) => Err(Shared { inner }),
+        }
+    }
+
+    /// Move the cursor of the display to (`x`, `y`), unless it is already
+    /// there.
+    fn goto(&self, x: u32, y: u32) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.cursor != Some((x, y)) {
+            inner.screen.gotoxy(x, y)?;
+            inner.cursor = Some((x, y));
+        }
+        Ok(())
+    }
+
+    /// Write a character at the cursor of the display, moving it right.
+    fn put(&self, c: u8) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.screen.write_all(&[c])?;
+        // the cursor wraps at the end of the DDRAM line
+        inner.cursor = match inner.cursor {
+            Some((x, y)) if x + 1 < DDRAM_LINE_WIDTH => Some((x + 1, y)),
+            _ => None,
+        };
+        Ok(())
+    }
+}
+
+impl<T> Write for Shared<T>
+where
+    T: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut inner = self.inner.borrow_mut();
+        inner.cursor = None;
+        inner.screen.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.borrow_mut().screen.flush()
+    }
+}
+
+/// A rectangle of a display, with its own cursor.
+pub struct Window<T> {
+    shared: Shared<T>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    parser: Parser,
+    // cursor, relative to the window
+    cx: u32,
+    cy: u32,
+}
+
+impl<T> Window<T>
+where
+    T: Write,
+{
+    /// Create a new [`Window`] of the `width` x `height` rectangle of
+    /// `shared` with its top-left corner at the (`x`, `y`) position.
+    ///
+    /// The cursor of the window starts at its top-left corner.
+    pub fn new(shared: Shared<T>, x: u32, y: u32, width: u32, height: u32) -> Window<T> {
+        Window {
+            shared,
+            x,
+            y,
+            width,
+            height,
+            parser: Parser::new(),
+            cx: 0,
+            cy: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Position of the cursor relative to the window, which may be out of
+    /// the window.
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.cx, self.cy)
+    }
+
+    /// Unwrap this window, returning the handle to the shared screen.
+    pub fn into_inner(self) -> Shared<T> {
+        self.shared
+    }
+
+    fn is_inside(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Move the cursor of the display to the cursor of the window, if it is
+    /// in the window.
+    fn sync(&mut self) -> Result<()> {
+        if self.is_inside(self.cx, self.cy) {
+            self.shared.goto(self.x + self.cx, self.y + self.cy)?;
+        }
+        Ok(())
+    }
+
+    /// Blank line `y` of the window from column `x`.
+    fn blank(&mut self, x: u32, y: u32) -> Result<()> {
+        if self.is_inside(x, y) {
+            self.shared.goto(self.x + x, self.y + y)?;
+            for _ in x..self.width {
+                self.shared.put(b' ')?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Char(c) => {
+                if self.is_inside(self.cx, self.cy) {
+                    self.sync()?;
+                    self.shared.put(c)?;
+                }
+                self.cx = self.cx.saturating_add(1);
+            }
+            Command::Back => {
+                self.cx = self.cx.saturating_sub(1);
+                if self.is_inside(self.cx, self.cy) {
+                    self.sync()?;
+                    self.shared.put(b' ')?;
+                }
+            }
+            Command::Clear | Command::Reinitialize => {
+                for y in 0..self.height {
+                    self.blank(0, y)?;
+                }
+                self.cx = 0;
+                self.cy = 0;
+            }
+            Command::NewLine => {
+                self.blank(self.cx, self.cy)?;
+                self.cx = 0;
+                self.cy = (self.cy + 1) % self.height.max(1);
+            }
+            Command::KillEndOfLine => self.blank(self.cx, self.cy)?,
+            Command::CarriageReturn => self.cx = 0,
+            Command::Home => {
+                self.cx = 0;
+                self.cy = 0;
+            }
+            Command::ShiftCursorLeft => self.cx = self.cx.saturating_sub(1),
+            Command::ShiftCursorRight => self.cx = self.cx.saturating_add(1),
+            Command::GotoXY(x, y) => {
+                self.cx = x.unwrap_or(self.cx);
+                self.cy = y.unwrap_or(self.cy);
+            }
+            Command::ShiftDisplayLeft | Command::ShiftDisplayRight | Command::Invalid(_) => {}
+            command => command.write_to(&mut self.shared.inner.borrow_mut().screen)?,
+        }
+        Ok(())
+    }
+}
+
+impl<T> Write for Window<T>
+where
+    T: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for &b in buf {
+            if let Some(command) = self.parser.push(b) {
+                self.apply(command)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Move the cursor of the display to the cursor of the window, then
+    /// flush the display.
+    fn flush(&mut self) -> Result<()> {
+        self.sync()?;
+        self.shared.inner.borrow_mut().screen.flush()
+    }
+}
+
+impl<T> Screen<T>
+where
+    T: Write,
+{
+    /// Share this screen between several windows, see [`Window`].
+    pub fn shared(self) -> Screen<Shared<T>> {
+        Screen::new(Shared::new(self))
+    }
+}
+
+impl<T> Screen<Shared<T>>
+where
+    T: Write,
+{
+    /// Get a screen drawing in the `width` x `height` rectangle of this
+    /// screen with its top-left corner at the (`x`, `y`) position, see
+    /// [`Window`].
+    ///
+    /// Windows of the same screen can be used at the same time, each of
+    /// them keeping its own cursor.
+    pub fn window(&self, x: u32, y: u32, width: u32, height: u32) -> Screen<Window<T>> {
+        Screen::new(self.get_ref().window(x, y, width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shadow::Shadow;
+
+    /// Lines of the display, separated by new lines.
+    fn lines(screen: &Screen<Shared<Shadow<Vec<u8>>>>) -> Vec<u8> {
+        let shared = screen.get_ref().screen();
+        let snapshot = shared.get_ref().snapshot();
+        let lines: Vec<&[u8]> = (0..snapshot.height()).map(|y| snapshot.line(y)).collect();
+        lines.join(&b'\n')
+    }
+
+    #[test]
+    fn clip_and_translate() {
+        let screen = Screen::new(Vec::new()).shared();
+        let mut window = screen.window(2, 1, 4, 2);
+        window.gotoxy(1, 0).unwrap();
+        window.write_all(b"abcdef\tg").unwrap();
+        window.gotoxy(0, 5).unwrap();
+        window.write_all(b"h").unwrap();
+        window.flush().unwrap();
+        assert_eq!(window.get_ref().cursor(), (1, 5));
+
+        assert_eq!(screen.get_ref().screen().get_ref(), b"\x1b[Lx3y1;abc");
+    }
+
+    #[test]
+    fn blank_within_window() {
+        let mut screen = Screen::new(Vec::new()).shadowed(8, 3).shared();
+        screen.write_all(b"12345678\n12345678\n12345678").unwrap();
+
+        let mut window = screen.window(2, 0, 4, 2);
+        window.clear().unwrap();
+        window.write_all(b"ab\ncdef").unwrap();
+        window.gotoxy(1, 1).unwrap();
+        window.kill_eol().unwrap();
+        window.flush().unwrap();
+
+        assert_eq!(lines(&screen), b"12ab  78\n12c   78\n12345678");
+        let shared = screen.get_ref().screen();
+        assert_eq!(shared.get_ref().snapshot().cursor(), (3, 1));
+    }
+
+    #[test]
+    fn status_bar_and_main_area() {
+        fn draw<T: Write>(screen: &mut Screen<T>, text: &[u8]) -> Result<()> {
+            screen.clear()?;
+            screen.write_all(text)
+        }
+
+        let screen = Screen::new(Vec::new()).shadowed(8, 3).shared();
+        draw(&mut screen.window(0, 0, 8, 1), b"12:34 ok").unwrap();
+        draw(&mut screen.window(0, 1, 8, 2), b"Hello\nworld").unwrap();
+        draw(&mut screen.window(0, 1, 8, 2), b"Bye").unwrap();
+
+        assert_eq!(lines(&screen), b"12:34 ok\nBye     \n        ");
+    }
+
+    #[test]
+    fn interleaved_windows() {
+        let screen = Screen::new(Vec::new()).shadowed(8, 2).shared();
+        let mut status = screen.window(0, 0, 8, 1);
+        let mut main = screen.window(2, 1, 6, 1);
+
+        status.write_all(b"12").unwrap();
+        main.write_all(b"Hel").unwrap();
+        status.write_all(b":34").unwrap();
+        main.write_all(b"lo").unwrap();
+        status.write_all(b"abc").unwrap();
+        main.flush().unwrap();
+
+        assert_eq!(lines(&screen), b"12:34abc\n  Hello ");
+        let shared = screen.get_ref().screen();
+        assert_eq!(shared.get_ref().snapshot().cursor(), (7, 1));
+    }
+}